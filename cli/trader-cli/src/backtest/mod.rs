@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    sync::Arc,
+};
+
+use flate2::read::GzDecoder;
+use pyo3::{exceptions::PyValueError, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    exchange::{Book, Level, MarketTrade},
+    position::Position,
+    rules::SymbolRules,
+    sim::{run_sim, FillModel, SimEvent, SimExchange},
+    trader::TraderSummary,
+};
+
+// -----
+// a recording is JSONL, gzipped if it ends in .gz, one record per line in the order it was received:
+//  {"type": "rules", "symbol": "BTCUSDT", "rules": {"tick_size": 0.1, ...}}
+//  {"type": "book", "symbol": "BTCUSDT", "event_time": 1727171378371, "bids": [[px, qty], ..], "asks": [..]}
+//  {"type": "trade", "symbol": "BTCUSDT", "trade_time": 1727171378370, "px": 63000.0, "qty": 0.002}
+// any other type is skipped, see recorder for what a recording run writes
+// -----
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record {
+    Rules {
+        symbol: String,
+        rules: SymbolRules,
+    },
+    Book {
+        symbol: String,
+        event_time: u64,
+        bids: Vec<(f64, f64)>,
+        asks: Vec<(f64, f64)>,
+    },
+    Trade {
+        symbol: String,
+        trade_time: u64,
+        px: f64,
+        qty: f64,
+    },
+    #[serde(other)]
+    Other,
+}
+
+fn levels(levels: Vec<(f64, f64)>) -> Vec<Level> {
+    levels
+        .into_iter()
+        .map(|(px, qty)| Level { px: px, qty: qty })
+        .collect()
+}
+
+pub fn load_recording(path: &str) -> Result<(HashMap<String, SymbolRules>, Vec<SimEvent>), String> {
+    let file = File::open(path).map_err(|e| format!("couldnt open {}: {}", path, e))?;
+    let reader: Box<dyn BufRead> = match path.ends_with(".gz") {
+        true => Box::new(BufReader::new(GzDecoder::new(file))),
+        false => Box::new(BufReader::new(file)),
+    };
+    let mut rules: HashMap<String, SymbolRules> = HashMap::new();
+    let mut script: Vec<SimEvent> = vec![];
+    for (idx, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                // a recording killed before it was finished, replay what made it to disk
+                println!("{} is cut short after line {}: {}", path, idx, e);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(&line)
+            .map_err(|e| format!("{} line {} is not a record: {}", path, idx + 1, e))?;
+        match record {
+            Record::Rules {
+                symbol,
+                rules: symbol_rules,
+            } => {
+                rules.insert(symbol, symbol_rules);
+            }
+            Record::Book {
+                symbol,
+                event_time,
+                bids,
+                asks,
+            } => script.push(SimEvent::Book(Book {
+                symbol: symbol,
+                event_time: event_time,
+                bids: levels(bids),
+                asks: levels(asks),
+            })),
+            Record::Trade {
+                symbol,
+                trade_time,
+                px,
+                qty,
+            } => script.push(SimEvent::Trade(MarketTrade {
+                symbol: symbol,
+                trade_time: trade_time,
+                px: px,
+                qty: qty,
+            })),
+            Record::Other => {}
+        }
+    }
+    Ok((rules, script))
+}
+
+#[pyfunction]
+#[pyo3(signature = (positions, path, latency_ms = 0, queue_fraction = 1.0))]
+pub fn run_backtest(
+    positions: Vec<Position>,
+    path: &str,
+    latency_ms: u64,
+    queue_fraction: f64,
+) -> PyResult<Vec<TraderSummary>> {
+    let fill_model = FillModel {
+        latency_ms: latency_ms,
+        queue_fraction: queue_fraction,
+    };
+    replay(positions, path, fill_model).map_err(PyValueError::new_err)
+}
+
+pub fn replay(
+    positions: Vec<Position>,
+    path: &str,
+    fill_model: FillModel,
+) -> Result<Vec<TraderSummary>, String> {
+    // replays the recording through the traders against the simulated exchange, starting flat.
+    // the summaries are the ones run_binance returns
+    if !(0.0..=1.0).contains(&fill_model.queue_fraction) {
+        return Err(format!(
+            "Invalid queue_fraction {}, expected 0 <= queue_fraction <= 1",
+            fill_model.queue_fraction
+        ));
+    }
+    for position in positions.iter() {
+        position.validate()?;
+    }
+    let (rules, script) = load_recording(path)?;
+    for position in positions.iter() {
+        // the filters as they were when recording, never the current ones
+        if !rules.contains_key(&position.symbol) {
+            return Err(format!("{} has no rules in {}", position.symbol, path));
+        }
+    }
+    run_sim(
+        positions,
+        Arc::new(SimExchange::new(rules, fill_model)),
+        script,
+        "cancel",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_fill_model() {
+        let path = std::env::temp_dir().join(format!("backtest-{}.jsonl", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        let book = |event_time: u64| {
+            serde_json::json!({"type": "book", "symbol": "BTCUSDT", "event_time": event_time,
+                "bids": [[100.0, 3.0]], "asks": [[100.1, 3.0]]})
+        };
+        let mut records = vec![
+            serde_json::json!({"type": "rules", "symbol": "BTCUSDT", "rules": {
+                "tick_size": 0.1, "min_price": 1.0, "max_price": 1000000.0, "step_size": 0.001,
+                "min_qty": 0.001, "max_qty": 1000.0, "min_notional": 5.0, "contract_size": null
+            }}),
+            book(0),
+            serde_json::json!({"type": "raw", "stream": "btcusdt@depth5@100ms"}),
+        ];
+        for trade_time in [100, 200, 300, 400] {
+            records.push(serde_json::json!({"type": "trade", "symbol": "BTCUSDT",
+                "trade_time": trade_time, "px": 100.0, "qty": 1.0}));
+        }
+        records.push(book(500));
+        for record in records {
+            writeln!(file, "{}", record).unwrap();
+        }
+        let path = path.to_str().unwrap();
+
+        let position = Position {
+            symbol: "BTCUSDT".to_string(),
+            notional: 0.0,
+            reduce_only: false,
+            sz_type: "q".to_string(),
+            qty: 1.0,
+            ..Default::default()
+        };
+        let completed_at = |latency_ms: u64, queue_fraction: f64| {
+            let fill_model = FillModel {
+                latency_ms: latency_ms,
+                queue_fraction: queue_fraction,
+            };
+            let summary = replay(vec![position.clone()], path, fill_model).unwrap();
+            assert_eq!(summary[0].status, "filled");
+            assert_eq!(summary[0].avg_entry, Some(100.0));
+            summary[0].completed_at
+        };
+        // behind the 3.0 shown at the bid, at the front of it, and at the front but late
+        assert_eq!(completed_at(0, 1.0), Some(400));
+        assert_eq!(completed_at(0, 0.0), Some(100));
+        assert_eq!(completed_at(150, 0.0), Some(200));
+
+        // raised as ValueError by run_backtest
+        let fill_model = FillModel::default();
+        let unrecorded = Position {
+            symbol: "ETHUSDT".to_string(),
+            ..position.clone()
+        };
+        assert!(replay(vec![unrecorded], path, fill_model).is_err());
+        assert!(replay(vec![position.clone()], "missing.jsonl", fill_model).is_err());
+        let fill_model = FillModel {
+            latency_ms: 0,
+            queue_fraction: 1.5,
+        };
+        assert!(replay(vec![position.clone()], path, fill_model).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
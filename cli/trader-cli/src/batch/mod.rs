@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    exchange::{Exchange, ExchangeError, OrderRequest},
+    rest::Rest,
+    schedule::Urgency,
+    trader::SymbolTrader,
+};
+
+const FLUSH_INTERVAL: Duration = Duration::from_millis(20);
+const MAX_BATCH_ORDERS: usize = 5;
+const MAX_BATCH_CANCELS: usize = 10;
+
+struct QueuedOrder {
+    order: OrderRequest,
+    client_order_id: String,
+    px: f64,
+    urgency: Urgency,
+}
+
+// coalesces the placements and cancels of every trader into batchOrders calls.
+// traders queue while holding their own lock, the flusher hands the responses back.
+// modifies are not batched, each amend is its own request
+pub struct BatchCoordinator {
+    rest: Rest,
+    orders: Mutex<Vec<QueuedOrder>>,
+    cancels: Mutex<Vec<(String, String)>>,
+}
+
+impl BatchCoordinator {
+    pub fn new(rest: Rest) -> Self {
+        BatchCoordinator {
+            rest: rest,
+            orders: Mutex::new(vec![]),
+            cancels: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn queue_order(
+        &self,
+        order: OrderRequest,
+        client_order_id: &str,
+        px: f64,
+        urgency: Urgency,
+    ) {
+        self.orders.lock().unwrap().push(QueuedOrder {
+            order: order,
+            client_order_id: client_order_id.to_string(),
+            px: px,
+            urgency: urgency,
+        });
+    }
+
+    pub fn queue_cancel(&self, symbol: &str, client_order_id: &str) {
+        self.cancels
+            .lock()
+            .unwrap()
+            .push((symbol.to_string(), client_order_id.to_string()));
+    }
+
+    fn flush(&self, traders: &HashMap<String, Arc<Mutex<SymbolTrader>>>) {
+        // cancels first, they free up margin and the book for the new orders
+        let cancels: Vec<(String, String)> = self.cancels.lock().unwrap().drain(..).collect();
+        let mut by_symbol: HashMap<String, Vec<String>> = HashMap::new();
+        for (symbol, client_order_id) in cancels {
+            by_symbol.entry(symbol).or_default().push(client_order_id);
+        }
+        for (symbol, client_order_ids) in by_symbol {
+            for chunk in client_order_ids.chunks(MAX_BATCH_CANCELS) {
+                let responses = match chunk.len() {
+                    1 => vec![self.rest.cancel_order(&symbol, &chunk[0])],
+                    _ => match self.rest.batch_cancel(&symbol, chunk) {
+                        Ok(responses) => responses,
+                        Err(e) => {
+                            let e = batch_error(e);
+                            chunk.iter().map(|_| Err(e.clone())).collect()
+                        }
+                    },
+                };
+                let mut trader = traders[&symbol].lock().unwrap();
+                for response in responses {
+                    trader.on_cancel_response(response);
+                }
+            }
+        }
+
+        let mut orders: Vec<QueuedOrder> = self.orders.lock().unwrap().drain(..).collect();
+        while !orders.is_empty() {
+            let chunk: Vec<QueuedOrder> = orders
+                .drain(..orders.len().min(MAX_BATCH_ORDERS))
+                .collect();
+            let meta: Vec<(String, String, f64, Urgency)> = chunk
+                .iter()
+                .map(|queued| {
+                    (
+                        queued.order.symbol.to_string(),
+                        queued.client_order_id.to_string(),
+                        queued.px,
+                        queued.urgency,
+                    )
+                })
+                .collect();
+            let mut requests: Vec<(OrderRequest, String)> = chunk
+                .into_iter()
+                .map(|queued| (queued.order, queued.client_order_id))
+                .collect();
+            let responses = match requests.len() {
+                1 => {
+                    let (order, client_order_id) = requests.remove(0);
+                    vec![self.rest.place_order(&order, &client_order_id)]
+                }
+                _ => match self.rest.batch_orders(&requests) {
+                    Ok(responses) => responses,
+                    Err(e) => {
+                        let e = batch_error(e);
+                        requests.iter().map(|_| Err(e.clone())).collect()
+                    }
+                },
+            };
+            for ((symbol, client_order_id, px, urgency), response) in
+                meta.into_iter().zip(responses)
+            {
+                traders[&symbol].lock().unwrap().on_order_response(
+                    response,
+                    &client_order_id,
+                    px,
+                    urgency,
+                );
+            }
+        }
+    }
+}
+
+fn batch_error(e: ExchangeError) -> ExchangeError {
+    // a failed call says nothing about the single orders unless binance rejected it as a whole,
+    // each order is then looked up by its client order id like after a timeout
+    match e {
+        ExchangeError::Other(msg) => {
+            println!("batch call failed, {}", msg);
+            ExchangeError::UnknownStatus
+        }
+        e => e,
+    }
+}
+
+pub fn start_flusher(
+    batch: Arc<BatchCoordinator>,
+    traders: HashMap<String, Arc<Mutex<SymbolTrader>>>,
+    running: Arc<AtomicBool>,
+) {
+    thread::spawn(move || {
+        while running.load(std::sync::atomic::Ordering::Acquire) {
+            thread::sleep(FLUSH_INTERVAL);
+            batch.flush(&traders);
+        }
+    });
+}
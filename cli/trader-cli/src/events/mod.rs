@@ -0,0 +1,158 @@
+use binance::{
+    futures::model::OrderTradeEvent,
+    model::{AggrTradesEvent, Asks, Bids, DepthOrderBookEvent},
+};
+use serde::Deserialize;
+
+use crate::exchange::{Book, Level, MarketTrade, OrderEvent};
+
+// the binance stream events as the trader sees them
+
+fn parse(value: &str) -> f64 {
+    value
+        .parse::<f64>()
+        .expect("parsable string. wont fail unless binance breaks it")
+}
+
+impl From<DepthOrderBookEvent> for Book {
+    fn from(event: DepthOrderBookEvent) -> Self {
+        Book {
+            symbol: event.symbol,
+            event_time: event.event_time,
+            bids: event
+                .bids
+                .iter()
+                .map(|x: &Bids| Level {
+                    px: x.price,
+                    qty: x.qty,
+                })
+                .collect(),
+            asks: event
+                .asks
+                .iter()
+                .map(|x: &Asks| Level {
+                    px: x.price,
+                    qty: x.qty,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<AggrTradesEvent> for MarketTrade {
+    fn from(event: AggrTradesEvent) -> Self {
+        MarketTrade {
+            symbol: event.symbol,
+            trade_time: event.trade_order_time,
+            px: parse(&event.price),
+            qty: parse(&event.qty),
+        }
+    }
+}
+
+impl From<OrderTradeEvent> for OrderEvent {
+    fn from(event: OrderTradeEvent) -> Self {
+        OrderEvent {
+            symbol: event.order.symbol,
+            order_id: event.order.order_id,
+            execution_type: event.order.execution_type,
+            order_status: event.order.order_status,
+            last_filled_qty: parse(&event.order.qty_last_filled_trade),
+            last_filled_px: parse(&event.order.price_last_filled_trade),
+            average_px: parse(&event.order.average_price),
+            event_time: event.event_time,
+            transaction_time: event.transaction_time,
+        }
+    }
+}
+
+// executionReport on the spot user data stream
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpotExecutionReport {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "x")]
+    pub execution_type: String,
+    #[serde(rename = "X")]
+    pub order_status: String,
+    #[serde(rename = "i")]
+    pub order_id: u64,
+    #[serde(rename = "l")]
+    pub last_filled_qty: String,
+    #[serde(rename = "L")]
+    pub last_filled_px: String,
+    #[serde(rename = "z")]
+    pub cumulative_qty: String,
+    #[serde(rename = "Z")]
+    pub cumulative_quote_qty: String,
+    #[serde(rename = "T")]
+    pub transaction_time: u64,
+}
+
+impl From<SpotExecutionReport> for OrderEvent {
+    fn from(report: SpotExecutionReport) -> Self {
+        // spot has no average price on the event, it is cumulative quote / cumulative qty
+        let cumulative_qty = parse(&report.cumulative_qty);
+        let average_px = match cumulative_qty > 0.0 {
+            true => parse(&report.cumulative_quote_qty) / cumulative_qty,
+            false => 0.0,
+        };
+        // a rejected or self-trade prevented order is gone like an expired one
+        let execution_type = match report.execution_type.as_str() {
+            "REJECTED" | "TRADE_PREVENTION" => "EXPIRED".to_string(),
+            _ => report.execution_type,
+        };
+        let order_status = match report.order_status.as_str() {
+            "REJECTED" | "EXPIRED_IN_MATCH" | "PENDING_CANCEL" => "EXPIRED".to_string(),
+            _ => report.order_status,
+        };
+        OrderEvent {
+            symbol: report.symbol,
+            order_id: report.order_id,
+            execution_type: execution_type,
+            order_status: order_status,
+            last_filled_qty: parse(&report.last_filled_qty),
+            last_filled_px: parse(&report.last_filled_px),
+            average_px: average_px,
+            event_time: report.event_time,
+            transaction_time: report.transaction_time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spot_execution_report() {
+        let report: SpotExecutionReport = serde_json::from_value(serde_json::json!({
+            "e": "executionReport",
+            "E": 1727171378371_u64,
+            "s": "BTCUSDT",
+            "c": "rt-1f3a9c2e-0-1",
+            "S": "BUY",
+            "o": "LIMIT_MAKER",
+            "f": "GTC",
+            "q": "0.00200000",
+            "p": "63000.00000000",
+            "x": "TRADE",
+            "X": "PARTIALLY_FILLED",
+            "i": 4293153,
+            "l": "0.00100000",
+            "z": "0.00100000",
+            "L": "63000.00000000",
+            "Z": "63.00000000",
+            "T": 1727171378370_u64,
+            "C": ""
+        }))
+        .unwrap();
+        let event = OrderEvent::from(report);
+        assert_eq!(event.order_id, 4293153);
+        assert_eq!(event.last_filled_qty, 0.001);
+        assert_eq!(event.average_px, 63000.0);
+        assert_eq!(event.order_status, "PARTIALLY_FILLED");
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt::{self, Debug};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+// -----
+// what the trader sees of a venue. rest::Rest is the binance implementation, events converts the
+// binance stream events. error codes are binance futures codes, other venues map theirs to them
+// -----
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    pub px: f64,
+    pub qty: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Book {
+    pub symbol: String,
+    pub event_time: u64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+impl Book {
+    pub fn best_bid(&self) -> f64 {
+        self.bids.get(0).unwrap().px
+    }
+
+    pub fn best_ask(&self) -> f64 {
+        self.asks.get(0).unwrap().px
+    }
+}
+
+// a print on the public tape
+#[derive(Debug, Clone)]
+pub struct MarketTrade {
+    pub symbol: String,
+    pub trade_time: u64,
+    pub px: f64,
+    pub qty: f64,
+}
+
+// an execution report on one of our orders
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderEvent {
+    pub symbol: String,
+    pub order_id: u64,
+    // in the futures vocabulary, see trader::OrderStatus
+    pub execution_type: String,
+    pub order_status: String,
+    pub last_filled_qty: f64,
+    pub last_filled_px: f64,
+    pub average_px: f64,
+    pub event_time: u64,
+    pub transaction_time: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderType {
+    Limit,
+    Market,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeInForce {
+    // no time in force on a limit order is GTC
+    Ioc,
+    // GTX on futures, LIMIT_MAKER on spot
+    PostOnly,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderRequest {
+    pub symbol: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub time_in_force: Option<TimeInForce>,
+    pub qty: f64,
+    pub price: Option<f64>,
+    // None where the venue doesn't take it, spot and hedge mode
+    pub reduce_only: Option<bool>,
+    // "LONG" or "SHORT" in hedge mode
+    pub position_side: Option<String>,
+}
+
+fn default_position_side() -> String {
+    "BOTH".to_string()
+}
+
+// the part of a new/modify/query order response the trader needs
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderAck {
+    pub order_id: u64,
+    pub client_order_id: String,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub orig_qty: f64,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub executed_qty: f64,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub price: f64,
+    pub side: String,
+    #[serde(rename = "type")]
+    pub order_type: String,
+    // not on spot orders
+    #[serde(default)]
+    pub reduce_only: bool,
+    // "BOTH" in one-way mode and on spot, which doesn't send it
+    #[serde(default = "default_position_side")]
+    pub position_side: String,
+    pub time_in_force: String,
+    pub status: String,
+    // spot acks a new order with transactTime
+    #[serde(default, alias = "transactTime")]
+    pub update_time: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionRisk {
+    pub symbol: String,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub position_amt: f64,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub mark_price: f64,
+    pub position_side: String,
+}
+
+pub fn string_to_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value.parse::<f64>().map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExchangeError {
+    // not sent or throttled by the venue, the caller retries next tick
+    RateLimited,
+    // timed out or 5xx, the request may or may not have been executed
+    UnknownStatus,
+    Rejected { code: i16, msg: String },
+    Other(String),
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExchangeError::RateLimited => write!(f, "rate limited"),
+            ExchangeError::UnknownStatus => write!(f, "execution status unknown"),
+            ExchangeError::Rejected { code, msg } => write!(f, "rejected with {} {}", code, msg),
+            ExchangeError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+pub trait Exchange: Debug + Send + Sync {
+    fn place_order(
+        &self,
+        order: &OrderRequest,
+        client_order_id: &str,
+    ) -> Result<OrderAck, ExchangeError>;
+
+    // checked like place_order but never reaches the book
+    fn test_order(&self, order: &OrderRequest, client_order_id: &str) -> Result<(), ExchangeError>;
+
+    // qty is the new total, including what already filled
+    fn modify_order(
+        &self,
+        symbol: &str,
+        client_order_id: &str,
+        side: Side,
+        qty: f64,
+        px: f64,
+    ) -> Result<OrderAck, ExchangeError>;
+
+    // -2011 if the order is not open anymore
+    fn cancel_order(&self, symbol: &str, client_order_id: &str) -> Result<(), ExchangeError>;
+
+    // -2013 if the order never made it to the exchange
+    fn query_order(&self, symbol: &str, client_order_id: &str) -> Result<OrderAck, ExchangeError>;
+
+    fn open_orders(&self, symbol: &str) -> Result<Vec<OrderAck>, ExchangeError>;
+
+    // one entry per position side, a single BOTH entry in one-way mode
+    fn position_risk(&self, symbol: &str) -> Result<Vec<PositionRisk>, ExchangeError>;
+}
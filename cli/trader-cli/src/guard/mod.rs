@@ -0,0 +1,92 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuardAction {
+    Pause,
+    Abort,
+}
+
+impl GuardAction {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "pause" => Ok(GuardAction::Pause),
+            "abort" => Ok(GuardAction::Abort),
+            _ => Err(format!("Invalid on_limit {:?}", s)),
+        }
+    }
+
+    pub fn from(s: &str) -> Self {
+        GuardAction::parse(s).unwrap()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PriceGuard {
+    // worst price we are willing to quote at
+    pub limit_px: f64,
+    pub is_long: bool,
+    pub action: GuardAction,
+}
+
+impl PriceGuard {
+    pub fn new(
+        is_long: bool,
+        limit_px: Option<f64>,
+        max_slippage_bps: Option<f64>,
+        arrival_px: f64,
+        action: GuardAction,
+    ) -> Option<Self> {
+        // the tighter of the hard limit and the max adverse move from the arrival price
+        let slippage_px = max_slippage_bps.map(|bps| match is_long {
+            true => arrival_px * (1.0 + bps / 10_000.0),
+            false => arrival_px * (1.0 - bps / 10_000.0),
+        });
+        let limit_px = match (limit_px, slippage_px) {
+            (Some(a), Some(b)) => match is_long {
+                true => Some(a.min(b)),
+                false => Some(a.max(b)),
+            },
+            (a, b) => a.or(b),
+        };
+        limit_px.map(|limit_px| PriceGuard {
+            limit_px: limit_px,
+            is_long: is_long,
+            action: action,
+        })
+    }
+
+    pub fn is_breached(&self, touch: f64) -> bool {
+        match self.is_long {
+            true => touch > self.limit_px,
+            false => touch < self.limit_px,
+        }
+    }
+
+    pub fn clamp(&self, px: f64) -> f64 {
+        match self.is_long {
+            true => px.min(self.limit_px),
+            false => px.max(self.limit_px),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_uses_tighter_limit() {
+        let guard =
+            PriceGuard::new(true, Some(101.0), Some(50.0), 100.0, GuardAction::Pause).unwrap();
+        assert!((guard.limit_px - 100.5).abs() < 1e-9);
+        assert!(!guard.is_breached(100.4));
+        assert!(guard.is_breached(100.6));
+        assert_eq!(guard.clamp(100.7), guard.limit_px);
+
+        let guard =
+            PriceGuard::new(false, Some(99.0), Some(50.0), 100.0, GuardAction::Abort).unwrap();
+        assert!((guard.limit_px - 99.5).abs() < 1e-9);
+        assert!(guard.is_breached(99.4));
+        assert_eq!(guard.clamp(99.0), guard.limit_px);
+
+        assert!(PriceGuard::new(true, None, None, 100.0, GuardAction::Pause).is_none());
+    }
+}
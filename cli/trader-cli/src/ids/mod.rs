@@ -0,0 +1,87 @@
+// every order this tool sends carries a client id starting with the prefix, so it can be found again
+// after a timeout or by a later run. binance allows ^[\.A-Z\:/a-z0-9_-]{1,36}$
+pub const CLIENT_ID_PREFIX: &str = "rt-";
+
+pub fn new_run_id(started_at: u64) -> String {
+    // the run's start in ms since the epoch, 11 hex digits until the year 5000, then 4 random ones
+    // for runs starting in the same ms
+    format!(
+        "{:011x}{}",
+        started_at,
+        &uuid::Uuid::new_v4().simple().to_string()[..4]
+    )
+}
+
+// the run an id of ours was sent by, None for ids of other tools
+pub fn run_id_of(client_order_id: &str) -> Option<&str> {
+    client_order_id
+        .strip_prefix(CLIENT_ID_PREFIX)
+        .and_then(|rest| rest.split('-').next())
+}
+
+// when the run an id of ours was sent by started, None for ids of other tools and of runs from
+// before the start was part of the id
+pub fn run_started_at_of(client_order_id: &str) -> Option<u64> {
+    run_id_of(client_order_id)
+        .filter(|run_id| run_id.len() == 15)
+        .and_then(|run_id| u64::from_str_radix(&run_id[..11], 16).ok())
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientOrderIds {
+    run_id: String,
+    symbol_idx: usize,
+    seq: u32,
+}
+
+impl ClientOrderIds {
+    pub fn new(run_id: &str, symbol_idx: usize) -> Self {
+        ClientOrderIds {
+            run_id: run_id.to_string(),
+            symbol_idx: symbol_idx,
+            seq: 0,
+        }
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    pub fn next(&mut self) -> String {
+        // rt-<run>-<symbol>-<child>, e.g. rt-1928c5b0e4a9f3c-4-17
+        self.seq += 1;
+        format!(
+            "{}{}-{}-{}",
+            CLIENT_ID_PREFIX, self.run_id, self.symbol_idx, self.seq
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_order_ids() {
+        let run_id = new_run_id(1727000000000);
+        let mut ids = ClientOrderIds::new(&run_id, 29);
+        let first = ids.next();
+        let second = ids.next();
+        assert_ne!(first, second);
+        assert!(first.starts_with(CLIENT_ID_PREFIX));
+        assert!(first.len() <= 36);
+        assert!(first
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".:/_-".contains(c)));
+        assert_eq!(second, format!("rt-{}-29-2", run_id));
+        assert_eq!(run_id_of(&second), Some(run_id.as_str()));
+        assert_eq!(run_id_of("web_abc"), None);
+        assert_eq!(run_started_at_of(&second), Some(1727000000000));
+        assert_eq!(run_started_at_of("rt-1f3a9c2e-0-3"), None);
+        assert_eq!(run_started_at_of("web_abc"), None);
+        // the longest id, the 1000th symbol's 99999999th child
+        let mut ids = ClientOrderIds::new(&run_id, 999);
+        ids.seq = 99999998;
+        assert!(ids.next().len() <= 36);
+    }
+}
@@ -1,16 +1,41 @@
+use backtest::run_backtest;
 use position::Position;
+use pricing::register_pricing_strategy;
 use pyo3::prelude::*;
+use recorder::record_market_data;
 use trader::run_binance;
 
+mod backtest;
+mod batch;
+mod events;
+mod exchange;
+mod guard;
+mod ids;
+mod paper;
+mod participation;
 mod position;
+mod position_target;
+mod pricing;
+mod ratelimit;
+mod reconcile;
+mod recorder;
+mod requote;
+mod rest;
+mod rules;
+mod schedule;
+mod sim;
 mod subscriber;
 mod trader;
 mod utils;
+mod venue;
 
 #[pymodule]
 #[pyo3(name = "rust_trader")]
 fn my_extension(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(run_binance, m)?)?;
+    m.add_function(wrap_pyfunction!(run_backtest, m)?)?;
+    m.add_function(wrap_pyfunction!(record_market_data, m)?)?;
+    m.add_function(wrap_pyfunction!(register_pricing_strategy, m)?)?;
     m.add_class::<Position>()?;
     Ok(())
 }
@@ -1,12 +1,32 @@
+mod backtest;
+mod batch;
+mod events;
+mod exchange;
+mod guard;
+mod ids;
+mod paper;
+mod participation;
 mod position;
+mod position_target;
+mod pricing;
+mod ratelimit;
+mod reconcile;
+mod recorder;
+mod requote;
+mod rest;
+mod rules;
+mod schedule;
+mod sim;
 mod subscriber;
 mod trader;
 mod utils;
+mod venue;
 
 use core::panic;
 
 use clap::{Parser, Subcommand};
 use position::Position;
+use venue::Venue;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -29,6 +49,21 @@ enum Commands {
         #[arg(long, required = true)]
         live: bool,
     },
+    // writes the public book and trade streams to a gzipped JSONL for run_backtest
+    Record {
+        #[arg(short, long, required = true)]
+        symbols: Vec<String>,
+
+        #[arg(long, default_value = "usdm")]
+        venue: String,
+
+        #[arg(short, long, required = true)]
+        path: String,
+
+        // until killed if not set
+        #[arg(long)]
+        seconds: Option<u64>,
+    },
 }
 
 fn parse_positions_from_input(val: &str) -> Result<Position, String> {
@@ -37,15 +72,23 @@ fn parse_positions_from_input(val: &str) -> Result<Position, String> {
 }
 
 fn main() {
-    panic!("untested");
     let cli = Cli::parse();
 
     match &cli.command {
         Some(Commands::Trade { positions, live }) => {
             // dbg!(&cli.command);
-            dbg!(&live);
+            dbg!(&positions, &live);
+            panic!("untested");
             // trader::run(positions.clone());
         }
+        Some(Commands::Record {
+            symbols,
+            venue,
+            path,
+            seconds,
+        }) => {
+            recorder::record(Venue::from(venue), symbols, path, *seconds, || false);
+        }
         None => {
             panic!("you did not pass an argument")
         }
@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use tungstenite::{connect, Message};
+
+use crate::{
+    recorder::Recorder,
+    sim::{deliver_events, on_market_event, SimExchange},
+    subscriber::{market_data_url, parse_market_data, set_read_timeout},
+    trader::SymbolTrader,
+    venue::Venue,
+};
+
+// -----
+// paper trading. the public streams drive the traders like in a live run but the orders go to a
+// SimExchange, the account is never touched. see sim for the fill rules, the trades are always
+// subscribed to since a print through an order's price fills it
+// -----
+
+const PAPER_READ_TIMEOUT: Duration = Duration::from_secs(5);
+const PAPER_RECONNECT_WAIT: Duration = Duration::from_secs(1);
+
+pub fn init_paper_stream(
+    traders: HashMap<String, Arc<Mutex<SymbolTrader>>>,
+    keep_running: Arc<AtomicBool>,
+    venue: Venue,
+    exchange: Arc<SimExchange>,
+    recorder: Option<Arc<Recorder>>,
+) {
+    let streams: Vec<String> = traders
+        .keys()
+        .flat_map(|symbol| {
+            let symbol = symbol.to_lowercase();
+            vec![
+                format!("{}@depth5@100ms", symbol),
+                format!("{}@aggTrade", symbol),
+            ]
+        })
+        .collect();
+    println!("paper trading on streams {:#?}", &streams);
+
+    // a dropped connection reconnects like the spot stream, the simulated orders keep resting and
+    // nothing fills while disconnected
+    let mut reconnecting = false;
+    while keep_running.load(std::sync::atomic::Ordering::Acquire) {
+        if reconnecting {
+            thread::sleep(PAPER_RECONNECT_WAIT);
+        }
+        reconnecting = true;
+        let mut socket = match connect(market_data_url(venue, &streams)) {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                println!("couldnt connect to the market data streams, {}", e);
+                continue;
+            }
+        };
+        // keep_running is checked between reads, also on a quiet stream
+        set_read_timeout(&socket, PAPER_READ_TIMEOUT);
+
+        while keep_running.load(std::sync::atomic::Ordering::Acquire) {
+            match socket.read() {
+                Ok(Message::Text(text)) => {
+                    let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    let stream = message["stream"].as_str().expect("combined stream name");
+                    let event = parse_market_data(venue, stream, &message["data"]);
+                    if let Some(recorder) = &recorder {
+                        recorder.raw(stream, &message["data"]);
+                        recorder.market_data(&event);
+                    }
+                    on_market_event(&exchange, &traders, event, recorder.as_deref());
+                }
+                Ok(Message::Close(frame)) => {
+                    println!("market data stream closed with {:?}, reconnecting", frame);
+                    break;
+                }
+                // pings are answered by tungstenite
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => {
+                    println!("market data stream broke with {}, reconnecting", e);
+                    break;
+                }
+            }
+        }
+    }
+    // the cancels of a shutdown
+    deliver_events(&exchange, &traders, recorder.as_deref());
+}
@@ -0,0 +1,70 @@
+#[derive(Debug, Clone)]
+pub struct ParticipationTracker {
+    // target fraction of the market volume, 0..1
+    pub rate: f64,
+    // prints before the first book tick are not counted
+    pub started_at: Option<u64>,
+    pub market_qty: f64,
+    pub market_notional: f64,
+}
+
+impl ParticipationTracker {
+    pub fn new(rate: f64) -> Self {
+        ParticipationTracker {
+            rate: rate,
+            started_at: None,
+            market_qty: 0.0,
+            market_notional: 0.0,
+        }
+    }
+
+    pub fn start(&mut self, timestamp: u64) {
+        if self.started_at.is_none() {
+            self.started_at = Some(timestamp);
+        }
+    }
+
+    pub fn on_market_trade(&mut self, timestamp: u64, qty: f64, notional: f64) {
+        match self.started_at {
+            Some(started_at) if timestamp >= started_at => {
+                self.market_qty += qty;
+                self.market_notional += notional;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn allowed_qty(&self) -> f64 {
+        self.market_qty * self.rate
+    }
+
+    pub fn allowed_notional(&self) -> f64 {
+        self.market_notional * self.rate
+    }
+
+    pub fn realized_rate(&self, filled_qty: f64) -> Option<f64> {
+        match self.market_qty > 0.0 {
+            true => Some(filled_qty.abs() / self.market_qty),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_participation_tracker() {
+        let mut tracker = ParticipationTracker::new(0.1);
+        tracker.on_market_trade(900, 50.0, 5000.0);
+        assert_eq!(tracker.allowed_qty(), 0.0);
+
+        tracker.start(1_000);
+        tracker.on_market_trade(1_000, 10.0, 1000.0);
+        tracker.on_market_trade(1_100, 10.0, 1100.0);
+        assert!((tracker.allowed_qty() - 2.0).abs() < 1e-9);
+        assert!((tracker.allowed_notional() - 210.0).abs() < 1e-9);
+        assert_eq!(tracker.realized_rate(-1.0), Some(0.05));
+    }
+}
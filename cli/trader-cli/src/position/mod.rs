@@ -1,11 +1,58 @@
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{guard::GuardAction, pricing, venue::Venue};
+
+fn default_pricing() -> String {
+    "join".to_string()
+}
+
+fn default_sz_type() -> String {
+    "n".to_string()
+}
+
+fn default_twap_slices() -> u32 {
+    10
+}
+
+fn default_on_limit() -> String {
+    "pause".to_string()
+}
+
+fn default_amend() -> bool {
+    true
+}
+
+fn default_venue() -> String {
+    "usdm".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizeType {
+    Notional,
+    Qty,
+}
+
+impl SizeType {
+    fn parse(s: &str) -> Result<Self, String> {
+        // same codes as the django Order.sz_type
+        match s {
+            "n" => Ok(SizeType::Notional),
+            "q" => Ok(SizeType::Qty),
+            _ => Err(format!("Invalid sz_type {:?}", s)),
+        }
+    }
+
+    fn from(s: &str) -> Self {
+        SizeType::parse(s).unwrap()
+    }
+}
 
 #[derive(Debug, serde::Deserialize, Clone)]
 #[pyclass]
 pub struct Position {
     // ---
-    // if you want to liquidate a position, overstate the notional
-    // tested with notional value at 2x the current position and has run fine
+    // notional/qty is a delta unless `target` is set, see below.
+    // to liquidate a position use target = true with a size of 0
     // ---
     #[pyo3(get, set)]
     pub symbol: String,
@@ -15,16 +62,318 @@ pub struct Position {
 
     #[pyo3(get, set)]
     pub reduce_only: bool,
+
+    // see pricing::from_spec, e.g. "join", "improve:1", "mid", "level:2". a custom strategy is
+    // registered before the Position is built
+    #[pyo3(get, set)]
+    #[serde(default = "default_pricing")]
+    pub pricing: String,
+
+    // "n" sizes the order with notional, "q" with qty (base asset, signed)
+    #[pyo3(get, set)]
+    #[serde(default = "default_sz_type")]
+    pub sz_type: String,
+
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub qty: f64,
+
+    // seconds after the first tick, the remainder is swept with a market order at the deadline
+    #[pyo3(get, set)]
+    pub deadline_secs: Option<f64>,
+
+    // seconds after the first tick to start quoting inside the spread, defaults to half the deadline
+    #[pyo3(get, set)]
+    pub escalate_after_secs: Option<f64>,
+
+    // releases the target in twap_slices equal slices over twap_secs
+    #[pyo3(get, set)]
+    pub twap_secs: Option<f64>,
+
+    #[pyo3(get, set)]
+    #[serde(default = "default_twap_slices")]
+    pub twap_slices: u32,
+
+    // 0..1, fraction of the slice interval each release time is randomly moved by
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub twap_jitter: f64,
+
+    // caps the size of each child order, in quote currency
+    #[pyo3(get, set)]
+    pub max_child_notional: Option<f64>,
+
+    // 0..1, caps cumulative fills at this fraction of the market volume traded since start
+    #[pyo3(get, set)]
+    pub pov_rate: Option<f64>,
+
+    // worst price to quote at, the tighter of the two applies
+    #[pyo3(get, set)]
+    pub limit_px: Option<f64>,
+
+    // max adverse move of the touch from the arrival mid
+    #[pyo3(get, set)]
+    pub max_slippage_bps: Option<f64>,
+
+    // "pause" pulls the order until the touch comes back, "abort" stops the trader
+    #[pyo3(get, set)]
+    #[serde(default = "default_on_limit")]
+    pub on_limit: String,
+
+    // requote hysteresis, see requote::RequotePolicy. the defaults requote on every stale tick
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub requote_ticks: u32,
+
+    #[pyo3(get, set)]
+    pub requote_delay_ms: Option<u64>,
+
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub min_rest_ms: u64,
+
+    // requote a resting order in place, falls back to cancel/replace when the modify is rejected
+    #[pyo3(get, set)]
+    #[serde(default = "default_amend")]
+    pub amend: bool,
+
+    // notional/qty is the position to end up with, the delta to the account's position is worked
+    // and sent reduce-only when it only shrinks the exposure. a delta through zero, target or not,
+    // is split into a reduce-only close and an opening leg
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub target: bool,
+
+    // "LONG" or "SHORT", only for accounts in hedge mode. inferred from the side and reduce_only
+    // when left out, with target from its sign, a target of 0 closes the side that is open
+    #[pyo3(get, set)]
+    pub position_side: Option<String>,
+
+    // "usdm", "coinm" or "spot". on coin-m qty is in contracts and notional in usd, spot quotes with
+    // LIMIT_MAKER and has no reduce-only, target, hedge mode, amend or batching
+    #[pyo3(get, set)]
+    #[serde(default = "default_venue")]
+    pub venue: String,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position {
+            symbol: String::new(),
+            notional: 0.0,
+            reduce_only: false,
+            pricing: default_pricing(),
+            sz_type: default_sz_type(),
+            qty: 0.0,
+            deadline_secs: None,
+            escalate_after_secs: None,
+            twap_secs: None,
+            twap_slices: default_twap_slices(),
+            twap_jitter: 0.0,
+            max_child_notional: None,
+            pov_rate: None,
+            limit_px: None,
+            max_slippage_bps: None,
+            on_limit: default_on_limit(),
+            requote_ticks: 0,
+            requote_delay_ms: None,
+            min_rest_ms: 0,
+            amend: default_amend(),
+            target: false,
+            position_side: None,
+            venue: default_venue(),
+        }
+    }
+}
+
+impl Position {
+    pub fn size_type(&self) -> SizeType {
+        SizeType::from(&self.sz_type)
+    }
+
+    pub fn venue(&self) -> Venue {
+        Venue::from(&self.venue)
+    }
+
+    pub fn is_long(&self) -> bool {
+        match self.size_type() {
+            SizeType::Notional => self.notional > 0.0,
+            SizeType::Qty => self.qty > 0.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // what the trader would otherwise panic on. checked again by run_binance, the fields can be
+        // set from python after construction
+        SizeType::parse(&self.sz_type)?;
+        let venue = Venue::parse(&self.venue)?;
+        GuardAction::parse(&self.on_limit)?;
+        pricing::from_spec(&self.pricing)?;
+        if !venue.has_positions()
+            && (self.reduce_only || self.target || self.position_side.is_some())
+        {
+            return Err(format!(
+                "{} reduce_only, target and position_side need a futures venue",
+                self.symbol
+            ));
+        }
+        if let Some(position_side) = &self.position_side {
+            if position_side != "LONG" && position_side != "SHORT" {
+                return Err(format!("Invalid position_side {:?}", position_side));
+            }
+        }
+        if let Some(pov_rate) = self.pov_rate {
+            if !(pov_rate > 0.0 && pov_rate <= 1.0) {
+                return Err(format!(
+                    "Invalid pov_rate {}, expected 0 < pov_rate <= 1",
+                    pov_rate
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.twap_jitter) {
+            return Err(format!(
+                "Invalid twap_jitter {}, expected 0 <= twap_jitter <= 1",
+                self.twap_jitter
+            ));
+        }
+        if self.twap_slices == 0 {
+            return Err("Invalid twap_slices 0, expected at least 1".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[pymethods]
 impl Position {
     #[new]
-    fn new(symbol: String, notional: f64, reduce_only: bool) -> Self {
-        Position {
+    #[pyo3(signature = (
+        symbol,
+        notional,
+        reduce_only,
+        pricing = default_pricing(),
+        sz_type = default_sz_type(),
+        qty = 0.0,
+        deadline_secs = None,
+        escalate_after_secs = None,
+        twap_secs = None,
+        twap_slices = default_twap_slices(),
+        twap_jitter = 0.0,
+        max_child_notional = None,
+        pov_rate = None,
+        limit_px = None,
+        max_slippage_bps = None,
+        on_limit = default_on_limit(),
+        requote_ticks = 0,
+        requote_delay_ms = None,
+        min_rest_ms = 0,
+        amend = default_amend(),
+        target = false,
+        position_side = None,
+        venue = default_venue(),
+    ))]
+    // one argument per field, the python keyword arguments
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        symbol: String,
+        notional: f64,
+        reduce_only: bool,
+        pricing: String,
+        sz_type: String,
+        qty: f64,
+        deadline_secs: Option<f64>,
+        escalate_after_secs: Option<f64>,
+        twap_secs: Option<f64>,
+        twap_slices: u32,
+        twap_jitter: f64,
+        max_child_notional: Option<f64>,
+        pov_rate: Option<f64>,
+        limit_px: Option<f64>,
+        max_slippage_bps: Option<f64>,
+        on_limit: String,
+        requote_ticks: u32,
+        requote_delay_ms: Option<u64>,
+        min_rest_ms: u64,
+        amend: bool,
+        target: bool,
+        position_side: Option<String>,
+        venue: String,
+    ) -> PyResult<Self> {
+        let position = Position {
             symbol: symbol,
             notional: notional,
             reduce_only: reduce_only,
+            pricing: pricing,
+            sz_type: sz_type,
+            qty: qty,
+            deadline_secs: deadline_secs,
+            escalate_after_secs: escalate_after_secs,
+            twap_secs: twap_secs,
+            twap_slices: twap_slices,
+            twap_jitter: twap_jitter,
+            max_child_notional: max_child_notional,
+            pov_rate: pov_rate,
+            limit_px: limit_px,
+            max_slippage_bps: max_slippage_bps,
+            on_limit: on_limit,
+            requote_ticks: requote_ticks,
+            requote_delay_ms: requote_delay_ms,
+            min_rest_ms: min_rest_ms,
+            amend: amend,
+            target: target,
+            position_side: position_side,
+            venue: venue,
+        };
+        // raised as ValueError instead of panicking across the ffi
+        position.validate().map_err(PyValueError::new_err)?;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let position = Position {
+            symbol: "BTCUSDT".to_string(),
+            notional: 100.0,
+            ..Default::default()
+        };
+        assert!(position.validate().is_ok());
+        let invalid = [
+            Position {
+                pov_rate: Some(0.0),
+                ..position.clone()
+            },
+            Position {
+                pov_rate: Some(1.5),
+                ..position.clone()
+            },
+            Position {
+                twap_jitter: -0.1,
+                ..position.clone()
+            },
+            Position {
+                twap_slices: 0,
+                ..position.clone()
+            },
+            Position {
+                venue: "spot".to_string(),
+                reduce_only: true,
+                ..position.clone()
+            },
+            Position {
+                position_side: Some("BOTH".to_string()),
+                ..position.clone()
+            },
+            Position {
+                pricing: "levl:2".to_string(),
+                ..position.clone()
+            },
+        ];
+        for position in invalid.iter() {
+            assert!(position.validate().is_err(), "{:?}", position);
         }
     }
 }
@@ -0,0 +1,116 @@
+// target-position mode, the trader works the difference between the target and the account's position
+
+pub fn resolve(current_qty: f64, target_qty: f64) -> (f64, bool) {
+    // returns the signed qty to trade and whether it can be sent reduce-only.
+    // a delta that only shrinks the exposure is reduce-only, one that grows or flips it isn't
+    let delta = target_qty - current_qty;
+    let shrinks = current_qty != 0.0
+        && target_qty.abs() < current_qty.abs()
+        && (target_qty == 0.0 || target_qty.signum() == current_qty.signum());
+    (delta, shrinks)
+}
+
+pub fn infer_position_side(is_long: bool, reduce_only: bool) -> &'static str {
+    // hedge mode, buying opens a LONG unless it closes a SHORT
+    match (is_long, reduce_only) {
+        (true, false) | (false, true) => "LONG",
+        (false, false) | (true, true) => "SHORT",
+    }
+}
+
+pub fn target_position_side(
+    target_qty: f64,
+    long_qty: f64,
+    short_qty: f64,
+) -> Result<&'static str, String> {
+    // hedge mode, a target without a position_side is on the side its sign points to. a target of 0
+    // closes the side that is open
+    match (target_qty > 0.0, target_qty < 0.0) {
+        (true, _) => Ok("LONG"),
+        (_, true) => Ok("SHORT"),
+        (false, false) => match (long_qty != 0.0, short_qty != 0.0) {
+            (true, true) => Err(
+                "holds a LONG and a SHORT position, a target of 0 needs a position_side"
+                    .to_string(),
+            ),
+            (false, true) => Ok("SHORT"),
+            // flat already otherwise
+            (_, false) => Ok("LONG"),
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Leg {
+    // signed qty
+    pub qty: f64,
+    pub reduce_only: bool,
+    pub completed_at: Option<u64>,
+}
+
+pub fn split_flip(current_qty: f64, delta: f64) -> Option<Vec<Leg>> {
+    // a delta that crosses zero closes exactly the existing size reduce-only, then opens the rest
+    let end_qty = current_qty + delta;
+    match current_qty != 0.0 && end_qty != 0.0 && end_qty.signum() != current_qty.signum() {
+        true => Some(vec![
+            Leg {
+                qty: -current_qty,
+                reduce_only: true,
+                completed_at: None,
+            },
+            Leg {
+                qty: end_qty,
+                reduce_only: false,
+                completed_at: None,
+            },
+        ]),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        // flatten a long
+        assert_eq!(resolve(0.5, 0.0), (-0.5, true));
+        // cut a short
+        assert_eq!(resolve(-2.0, -0.5), (1.5, true));
+        // add to a long
+        assert_eq!(resolve(0.5, 1.0), (0.5, false));
+        // flip
+        assert_eq!(resolve(0.5, -0.5), (-1.0, false));
+        // open from flat
+        assert_eq!(resolve(0.0, 1.0), (1.0, false));
+    }
+
+    #[test]
+    fn test_infer_position_side() {
+        assert_eq!(infer_position_side(true, false), "LONG");
+        assert_eq!(infer_position_side(false, true), "LONG");
+        assert_eq!(infer_position_side(false, false), "SHORT");
+        assert_eq!(infer_position_side(true, true), "SHORT");
+    }
+
+    #[test]
+    fn test_target_position_side() {
+        assert_eq!(target_position_side(1.0, 0.0, -2.0), Ok("LONG"));
+        assert_eq!(target_position_side(-1.0, 2.0, 0.0), Ok("SHORT"));
+        // flatten the open side
+        assert_eq!(target_position_side(0.0, 2.0, 0.0), Ok("LONG"));
+        assert_eq!(target_position_side(0.0, 0.0, -2.0), Ok("SHORT"));
+        assert!(target_position_side(0.0, 2.0, -2.0).is_err());
+    }
+
+    #[test]
+    fn test_split_flip() {
+        let legs = split_flip(0.5, -1.5).unwrap();
+        assert_eq!((legs[0].qty, legs[0].reduce_only), (-0.5, true));
+        assert_eq!((legs[1].qty, legs[1].reduce_only), (-1.0, false));
+        assert!(split_flip(0.5, -0.5).is_none());
+        assert!(split_flip(0.5, 1.0).is_none());
+        assert!(split_flip(0.0, -1.0).is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use pyo3::prelude::*;
+
+use crate::{exchange::Book, utils::Round};
+
+pub trait PricingStrategy: Debug + Send + Sync {
+    // price to rest the order at
+    fn quote(&self, book: &Book, is_long: bool, tick_size: f64) -> f64;
+
+    // whether a resting order at px should be cancelled and requoted
+    fn is_stale(&self, px: f64, book: &Book, is_long: bool, tick_size: f64) -> bool {
+        (px - self.quote(book, is_long, tick_size)).abs() >= tick_size / 2.0
+    }
+}
+
+fn is_worse_than_touch(px: f64, book: &Book, is_long: bool) -> bool {
+    match is_long {
+        true => px < book.best_bid(),
+        false => px > book.best_ask(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinTouch;
+
+impl PricingStrategy for JoinTouch {
+    fn quote(&self, book: &Book, is_long: bool, _tick_size: f64) -> f64 {
+        match is_long {
+            true => book.best_bid(),
+            false => book.best_ask(),
+        }
+    }
+
+    fn is_stale(&self, px: f64, book: &Book, is_long: bool, _tick_size: f64) -> bool {
+        is_worse_than_touch(px, book, is_long)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImproveTicks {
+    pub ticks: u32,
+}
+
+impl PricingStrategy for ImproveTicks {
+    fn quote(&self, book: &Book, is_long: bool, tick_size: f64) -> f64 {
+        // step inside the spread by up to n ticks, never crossing it
+        let improvement = tick_size * self.ticks as f64;
+        let (bid, ask) = (book.best_bid(), book.best_ask());
+        match is_long {
+            true => (bid + improvement)
+                .min(ask - tick_size)
+                .max(bid)
+                .round_to_step(tick_size),
+            false => (ask - improvement)
+                .max(bid + tick_size)
+                .min(ask)
+                .round_to_step(tick_size),
+        }
+    }
+
+    fn is_stale(&self, px: f64, book: &Book, is_long: bool, _tick_size: f64) -> bool {
+        // once we are the touch we should not keep outbidding ourselves
+        is_worse_than_touch(px, book, is_long)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MidPeg;
+
+impl PricingStrategy for MidPeg {
+    fn quote(&self, book: &Book, is_long: bool, tick_size: f64) -> f64 {
+        let (bid, ask) = (book.best_bid(), book.best_ask());
+        let mid = (bid + ask) / 2.0;
+        match is_long {
+            true => mid.floor_to_step(tick_size).min(ask - tick_size).max(bid),
+            false => mid.ceil_to_step(tick_size).max(bid + tick_size).min(ask),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelN {
+    // 0 is the touch
+    pub level: usize,
+}
+
+impl PricingStrategy for LevelN {
+    fn quote(&self, book: &Book, is_long: bool, _tick_size: f64) -> f64 {
+        match is_long {
+            true => {
+                let level = self.level.min(book.bids.len() - 1);
+                book.bids.get(level).unwrap().px
+            }
+            false => {
+                let level = self.level.min(book.asks.len() - 1);
+                book.asks.get(level).unwrap().px
+            }
+        }
+    }
+}
+
+// python callable taking (bids, asks, is_long, tick_size) and returning the price to quote. joins
+// the touch when it raises or doesn't return a float
+#[derive(Debug)]
+pub struct PyStrategy {
+    pub callable: PyObject,
+}
+
+impl PricingStrategy for PyStrategy {
+    fn quote(&self, book: &Book, is_long: bool, tick_size: f64) -> f64 {
+        let bids: Vec<(f64, f64)> = book.bids.iter().map(|x| (x.px, x.qty)).collect();
+        let asks: Vec<(f64, f64)> = book.asks.iter().map(|x| (x.px, x.qty)).collect();
+        let px = Python::with_gil(|py| {
+            self.callable
+                .call1(py, (bids, asks, is_long, tick_size))
+                .and_then(|px| px.extract::<f64>(py))
+        });
+        match px {
+            Ok(px) => px,
+            Err(e) => {
+                println!("custom pricing strategy failed, joining the touch: {}", e);
+                JoinTouch.quote(book, is_long, tick_size)
+            }
+        }
+    }
+}
+
+fn custom_strategies() -> &'static Mutex<HashMap<String, Arc<dyn PricingStrategy>>> {
+    static CUSTOM_STRATEGIES: OnceLock<Mutex<HashMap<String, Arc<dyn PricingStrategy>>>> =
+        OnceLock::new();
+    CUSTOM_STRATEGIES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn register_strategy(name: &str, strategy: Arc<dyn PricingStrategy>) {
+    custom_strategies()
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), strategy);
+}
+
+#[pyfunction]
+pub fn register_pricing_strategy(name: String, callable: PyObject) {
+    register_strategy(&name, Arc::new(PyStrategy { callable: callable }));
+}
+
+pub fn from_spec(spec: &str) -> Result<Arc<dyn PricingStrategy>, String> {
+    // "join", "improve:<ticks>", "mid", "level:<n>" or a name given to register_strategy
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next().unwrap_or_default();
+    let arg = parts.next();
+    let parse_arg = || {
+        arg.ok_or(format!("pricing strategy {} needs an argument", spec))?
+            .parse::<u32>()
+            .map_err(|e| format!("invalid pricing strategy {}: {}", spec, e))
+    };
+
+    match name {
+        "join" => Ok(Arc::new(JoinTouch)),
+        "improve" => Ok(Arc::new(ImproveTicks {
+            ticks: parse_arg()?,
+        })),
+        "mid" => Ok(Arc::new(MidPeg)),
+        "level" => Ok(Arc::new(LevelN {
+            level: parse_arg()? as usize,
+        })),
+        _ => match custom_strategies().lock().unwrap().get(name) {
+            Some(strategy) => Ok(Arc::clone(strategy)),
+            None => Err(format!("unknown pricing strategy {}", spec)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::Level;
+
+    fn book(bids: &[f64], asks: &[f64]) -> Book {
+        Book {
+            symbol: "BTCUSDT".to_string(),
+            event_time: 0,
+            bids: bids.iter().map(|px| Level { px: *px, qty: 1.0 }).collect(),
+            asks: asks.iter().map(|px| Level { px: *px, qty: 1.0 }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_improve_ticks_stays_passive() {
+        let strategy = ImproveTicks { ticks: 3 };
+        let wide = book(&[100.0, 99.9], &[100.5, 100.6]);
+        assert_eq!(strategy.quote(&wide, true, 0.1), 100.3);
+        assert_eq!(strategy.quote(&wide, false, 0.1), 100.2);
+
+        let tight = book(&[100.0, 99.9], &[100.1, 100.2]);
+        assert_eq!(strategy.quote(&tight, true, 0.1), 100.0);
+        assert_eq!(strategy.quote(&tight, false, 0.1), 100.1);
+    }
+
+    #[test]
+    fn test_mid_peg_and_level() {
+        let wide = book(&[100.0, 99.9, 99.8], &[100.5, 100.6]);
+        assert_eq!(MidPeg.quote(&wide, true, 0.1), 100.2);
+        assert_eq!(MidPeg.quote(&wide, false, 0.1), 100.3);
+        assert!(MidPeg.is_stale(100.0, &wide, true, 0.1));
+        assert!(!MidPeg.is_stale(100.2, &wide, true, 0.1));
+
+        assert_eq!(LevelN { level: 2 }.quote(&wide, true, 0.1), 99.8);
+        assert_eq!(LevelN { level: 5 }.quote(&wide, false, 0.1), 100.6);
+    }
+
+    #[test]
+    fn test_from_spec() {
+        assert!(from_spec("join").is_ok());
+        assert!(from_spec("improve:2").is_ok());
+        assert!(from_spec("improve").is_err());
+        assert!(from_spec("nope").is_err());
+
+        register_strategy("always_join", Arc::new(JoinTouch));
+        assert!(from_spec("always_join").is_ok());
+    }
+}
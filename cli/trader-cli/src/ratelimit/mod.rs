@@ -0,0 +1,221 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// per account/ip, see exchangeInfo.rateLimits. 0 means the venue has no such limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimits {
+    pub weight_1m: u32,
+    pub orders_10s: u32,
+    pub orders_1m: u32,
+}
+
+impl RateLimits {
+    pub const USDM: RateLimits = RateLimits {
+        weight_1m: 2400,
+        orders_10s: 300,
+        orders_1m: 1200,
+    };
+    pub const SPOT: RateLimits = RateLimits {
+        weight_1m: 6000,
+        orders_10s: 100,
+        orders_1m: 0,
+    };
+    pub const COINM: RateLimits = RateLimits {
+        weight_1m: 2400,
+        orders_10s: 0,
+        orders_1m: 1200,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestKind {
+    Cancel,
+    Order,
+    Query,
+}
+
+impl RequestKind {
+    fn headroom(&self) -> f64 {
+        // share of each limit a request kind may use, cancels get the reserve so we can always pull orders
+        match self {
+            RequestKind::Cancel => 1.0,
+            RequestKind::Order => 0.9,
+            RequestKind::Query => 0.8,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    // (sent at, weight, orders)
+    sent: VecDeque<(Instant, u32, u32)>,
+    // last values reported by X-MBX-USED-WEIGHT-1M / X-MBX-ORDER-COUNT-*
+    reported_weight_1m: (u32, Instant),
+    reported_orders_10s: (u32, Instant),
+    reported_orders_1m: (u32, Instant),
+    banned_until: Option<Instant>,
+    backoff: Duration,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        let now = Instant::now();
+        RateLimiter {
+            limits: limits,
+            state: Mutex::new(LimiterState {
+                sent: VecDeque::new(),
+                reported_weight_1m: (0, now),
+                reported_orders_10s: (0, now),
+                reported_orders_1m: (0, now),
+                banned_until: None,
+                backoff: Duration::from_secs(1),
+            }),
+        }
+    }
+
+    pub fn try_acquire(&self, kind: RequestKind, weight: u32, orders: u32) -> bool {
+        // a batch counts once towards the weight and once per order towards the order limits
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if state.banned_until.is_some_and(|until| now < until) {
+            return false;
+        }
+        while state
+            .sent
+            .front()
+            .is_some_and(|(sent_at, _, _)| now.duration_since(*sent_at) > Duration::from_secs(60))
+        {
+            state.sent.pop_front();
+        }
+
+        // the exchange count wins when it is higher than ours, e.g. other processes on the same key
+        let within = |reported: (u32, Instant), window: Duration| match now.duration_since(reported.1)
+            < window
+        {
+            true => reported.0,
+            false => 0,
+        };
+        let weight_1m = state
+            .sent
+            .iter()
+            .map(|(_, weight, _)| weight)
+            .sum::<u32>()
+            .max(within(state.reported_weight_1m, Duration::from_secs(60)));
+        let orders_10s = state
+            .sent
+            .iter()
+            .filter(|(sent_at, _, _)| now.duration_since(*sent_at) <= Duration::from_secs(10))
+            .map(|(_, _, orders)| orders)
+            .sum::<u32>()
+            .max(within(state.reported_orders_10s, Duration::from_secs(10)));
+        let orders_1m = state
+            .sent
+            .iter()
+            .map(|(_, _, orders)| orders)
+            .sum::<u32>()
+            .max(within(state.reported_orders_1m, Duration::from_secs(60)));
+
+        let headroom = kind.headroom();
+        let within_limit =
+            |used: u32, limit: u32| limit == 0 || (used as f64) <= limit as f64 * headroom;
+        let allowed = within_limit(weight_1m + weight, self.limits.weight_1m)
+            && (orders == 0
+                || (within_limit(orders_10s + orders, self.limits.orders_10s)
+                    && within_limit(orders_1m + orders, self.limits.orders_1m)));
+        if allowed {
+            state.sent.push_back((now, weight, orders));
+        }
+        allowed
+    }
+
+    pub fn update_usage(
+        &self,
+        weight_1m: Option<u32>,
+        orders_10s: Option<u32>,
+        orders_1m: Option<u32>,
+    ) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if let Some(weight_1m) = weight_1m {
+            state.reported_weight_1m = (weight_1m, now);
+        }
+        if let Some(orders_10s) = orders_10s {
+            state.reported_orders_10s = (orders_10s, now);
+        }
+        if let Some(orders_1m) = orders_1m {
+            state.reported_orders_1m = (orders_1m, now);
+        }
+        state.backoff = Duration::from_secs(1);
+    }
+
+    pub fn back_off(&self, retry_after: Option<Duration>) -> Duration {
+        // -1003/-1015, 429 or 418. without a Retry-After the wait doubles on every hit
+        let mut state = self.state.lock().unwrap();
+        let wait = retry_after.unwrap_or(state.backoff);
+        println!("rate limited, backing off for {:?}", wait);
+        state.banned_until = Some(Instant::now() + wait);
+        state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+        wait
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancels_get_the_reserve() {
+        let limiter = RateLimiter::new(RateLimits::USDM);
+        limiter.update_usage(Some(2200), Some(0), Some(0));
+        assert!(!limiter.try_acquire(RequestKind::Order, 1, 1));
+        assert!(limiter.try_acquire(RequestKind::Cancel, 1, 0));
+
+        let limiter = RateLimiter::new(RateLimits::USDM);
+        limiter.update_usage(Some(0), Some(270), Some(0));
+        assert!(!limiter.try_acquire(RequestKind::Order, 1, 1));
+        assert!(limiter.try_acquire(RequestKind::Cancel, 1, 0));
+    }
+
+    #[test]
+    fn test_spot_limits() {
+        // the spot order limit is tighter and there is no 1m order count
+        let limiter = RateLimiter::new(RateLimits::SPOT);
+        limiter.update_usage(Some(0), Some(85), Some(5000));
+        assert!(limiter.try_acquire(RequestKind::Order, 1, 1));
+        limiter.update_usage(Some(0), Some(90), Some(5000));
+        assert!(!limiter.try_acquire(RequestKind::Order, 1, 1));
+    }
+
+    #[test]
+    fn test_back_off() {
+        let limiter = RateLimiter::new(RateLimits::USDM);
+        assert_eq!(limiter.back_off(None), Duration::from_secs(1));
+        assert!(!limiter.try_acquire(RequestKind::Cancel, 1, 0));
+        assert_eq!(limiter.back_off(None), Duration::from_secs(2));
+        assert_eq!(
+            limiter.back_off(Some(Duration::from_secs(5))),
+            Duration::from_secs(5)
+        );
+        for _ in 0..10 {
+            limiter.back_off(None);
+        }
+        assert_eq!(limiter.back_off(None), MAX_BACKOFF);
+
+        // the ban running out, a reported usage resets the doubling
+        limiter.state.lock().unwrap().banned_until = Some(Instant::now());
+        assert!(limiter.try_acquire(RequestKind::Cancel, 1, 0));
+        limiter.update_usage(Some(1), None, None);
+        assert_eq!(limiter.back_off(None), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    exchange::OrderAck,
+    ids::{run_id_of, run_started_at_of, CLIENT_ID_PREFIX},
+};
+
+// what to do with orders a previous run left in the book, the run's policy and the outcome for
+// each leftover order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LeftoverAction {
+    Cancel,
+    Adopt,
+    Ignore,
+}
+
+impl LeftoverAction {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "cancel" => Ok(LeftoverAction::Cancel),
+            "adopt" => Ok(LeftoverAction::Adopt),
+            "ignore" => Ok(LeftoverAction::Ignore),
+            _ => Err(format!("Invalid leftover policy {:?}", s)),
+        }
+    }
+
+    pub fn from(s: &str) -> Self {
+        LeftoverAction::parse(s).unwrap()
+    }
+}
+
+pub fn is_ours(order: &OrderAck) -> bool {
+    order.client_order_id.starts_with(CLIENT_ID_PREFIX)
+}
+
+pub fn is_leftover(order: &OrderAck, run_id: &str, started_at: u64) -> bool {
+    // ours and from a run that started before this one, going by the start in the id. a run
+    // started after this one, e.g. another process on the same account, is left alone. ids without
+    // a start are from an older version, long gone
+    match run_id_of(&order.client_order_id) {
+        Some(order_run_id) => {
+            order_run_id != run_id
+                && run_started_at_of(&order.client_order_id)
+                    .is_none_or(|order_started_at| order_started_at < started_at)
+        }
+        None => false,
+    }
+}
+
+pub fn decide(
+    policy: LeftoverAction,
+    order: &OrderAck,
+    is_long: bool,
+    reduce_only: bool,
+    position_side: &str,
+    remaining_qty: f64,
+    adopted: bool,
+) -> LeftoverAction {
+    // for a leftover order, see is_leftover. orders placed by hand or by other tools never get here
+    match policy {
+        LeftoverAction::Ignore => LeftoverAction::Ignore,
+        LeftoverAction::Cancel => LeftoverAction::Cancel,
+        LeftoverAction::Adopt => {
+            // only an untouched passive order on the right side of the same position, no larger
+            // than what is left to trade, can stand in for our first child
+            let side = match is_long {
+                true => "BUY",
+                false => "SELL",
+            };
+            match !adopted
+                && order.side == side
+                && order.reduce_only == reduce_only
+                && order.position_side == position_side
+                && (order.time_in_force == "GTX" || order.order_type == "LIMIT_MAKER")
+                && order.executed_qty == 0.0
+                && order.orig_qty <= remaining_qty
+            {
+                true => LeftoverAction::Adopt,
+                false => LeftoverAction::Cancel,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leftover(client_order_id: &str, side: &str, executed_qty: f64) -> OrderAck {
+        OrderAck {
+            order_id: 1,
+            client_order_id: client_order_id.to_string(),
+            orig_qty: 0.01,
+            executed_qty: executed_qty,
+            price: 63000.0,
+            side: side.to_string(),
+            order_type: "LIMIT".to_string(),
+            reduce_only: false,
+            position_side: "BOTH".to_string(),
+            time_in_force: "GTX".to_string(),
+            status: "NEW".to_string(),
+            update_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_is_leftover() {
+        // a run started at 1000 ms, this one at 2000 ms
+        let order = leftover("rt-000000003e8a1b2-0-3", "BUY", 0.0);
+        assert!(is_leftover(&order, "000000007d0c3d4", 2000));
+        // this run's own, a manual one, or one of a run started after this one
+        assert!(!is_leftover(&order, "000000003e8a1b2", 1000));
+        let manual = leftover("web_abc", "BUY", 0.0);
+        assert!(!is_leftover(&manual, "000000007d0c3d4", 2000));
+        assert!(!is_leftover(&order, "00000000001c3d4", 1));
+        // ids from before the start was in them
+        let old = leftover("rt-1f3a9c2e-0-3", "BUY", 0.0);
+        assert!(is_leftover(&old, "000000007d0c3d4", 2000));
+    }
+
+    #[test]
+    fn test_decide() {
+        let order = leftover("rt-000000003e8a1b2-0-3", "BUY", 0.0);
+        assert_eq!(
+            decide(
+                LeftoverAction::Cancel,
+                &order,
+                true,
+                false,
+                "BOTH",
+                0.01,
+                false
+            ),
+            LeftoverAction::Cancel
+        );
+        assert_eq!(
+            decide(
+                LeftoverAction::Ignore,
+                &order,
+                true,
+                false,
+                "BOTH",
+                0.01,
+                false
+            ),
+            LeftoverAction::Ignore
+        );
+        assert_eq!(
+            decide(
+                LeftoverAction::Adopt,
+                &order,
+                true,
+                false,
+                "BOTH",
+                0.01,
+                false
+            ),
+            LeftoverAction::Adopt
+        );
+        // one adopted order per symbol, wrong side, oversized or partially filled ones are cancelled
+        assert_eq!(
+            decide(
+                LeftoverAction::Adopt,
+                &order,
+                true,
+                false,
+                "BOTH",
+                0.01,
+                true
+            ),
+            LeftoverAction::Cancel
+        );
+        assert_eq!(
+            decide(
+                LeftoverAction::Adopt,
+                &order,
+                false,
+                false,
+                "BOTH",
+                0.01,
+                false
+            ),
+            LeftoverAction::Cancel
+        );
+        assert_eq!(
+            decide(
+                LeftoverAction::Adopt,
+                &order,
+                true,
+                false,
+                "BOTH",
+                0.005,
+                false
+            ),
+            LeftoverAction::Cancel
+        );
+        // the other position of a hedge-mode account
+        let short = OrderAck {
+            position_side: "SHORT".to_string(),
+            ..order.clone()
+        };
+        assert_eq!(
+            decide(
+                LeftoverAction::Adopt,
+                &short,
+                true,
+                false,
+                "LONG",
+                0.01,
+                false
+            ),
+            LeftoverAction::Cancel
+        );
+        let partial = leftover("rt-000000003e8a1b2-0-3", "BUY", 0.005);
+        assert_eq!(
+            decide(
+                LeftoverAction::Adopt,
+                &partial,
+                true,
+                false,
+                "BOTH",
+                0.01,
+                false
+            ),
+            LeftoverAction::Cancel
+        );
+    }
+}
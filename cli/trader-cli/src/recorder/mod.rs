@@ -0,0 +1,293 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tungstenite::{connect, Message};
+
+use crate::{
+    exchange::{Book, Level, MarketTrade, OrderEvent},
+    ratelimit::RateLimiter,
+    rest::Rest,
+    rules::SymbolRules,
+    sim::SimEvent,
+    subscriber::{market_data_url, parse_market_data},
+    venue::Venue,
+};
+
+// -----
+// gzipped JSONL that backtest::load_recording replays, one record per line in the order it was
+// received, each stamped with the local receive time in ms:
+//  {"type": "raw", "recv_time": .., "stream": "btcusdt@depth5@100ms", "data": {..}}, the payload
+//   as received. spot, paper and record-only runs
+//  {"type": "parsed", "recv_time": .., "stream": "btcusdt@depth5@100ms", "data": {..}}, futures
+//   runs instead of raw. binance-rs hands over the parsed event only, data is it serialized again
+//   with the binance-rs field names, the stream name is rebuilt from the symbol
+//  {"type": "book", "recv_time": .., ...} and {"type": "trade", ...}, normalized as in backtest
+//  {"type": "order", "recv_time": .., "symbol": "BTCUSDT", "order_id": .., ...}, our own orders
+// -----
+
+// a hard kill loses at most this much
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct Sink {
+    encoder: GzEncoder<BufWriter<File>>,
+    flushed_at: Instant,
+    finished: bool,
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    sink: Mutex<Sink>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn levels(levels: &[Level]) -> Vec<(f64, f64)> {
+    levels.iter().map(|level| (level.px, level.qty)).collect()
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Self {
+        let file = File::create(path).expect(&format!("couldnt create {}", path));
+        println!("recording to {}", path);
+        Recorder {
+            sink: Mutex::new(Sink {
+                encoder: GzEncoder::new(BufWriter::new(file), Compression::default()),
+                flushed_at: Instant::now(),
+                finished: false,
+            }),
+        }
+    }
+
+    fn write(&self, record_type: &str, mut record: Value) {
+        record["type"] = json!(record_type);
+        record["recv_time"] = json!(now_ms());
+        let mut sink = self.sink.lock().unwrap();
+        if sink.finished {
+            // a late event from a stream that is shutting down
+            return;
+        }
+        writeln!(sink.encoder, "{}", record).expect("couldnt write the recording");
+        if sink.flushed_at.elapsed() >= FLUSH_INTERVAL {
+            sink.encoder.flush().expect("couldnt write the recording");
+            sink.flushed_at = Instant::now();
+        }
+    }
+
+    pub fn rules(&self, symbol: &str, rules: &SymbolRules) {
+        self.write("rules", json!({"symbol": symbol, "rules": rules}));
+    }
+
+    pub fn raw(&self, stream: &str, data: &serde_json::Value) {
+        self.write("raw", json!({"stream": stream, "data": data}));
+    }
+
+    pub fn parsed(&self, stream: &str, data: &impl Serialize) {
+        self.write("parsed", json!({"stream": stream, "data": data}));
+    }
+
+    pub fn book(&self, book: &Book) {
+        self.write(
+            "book",
+            json!({
+                "symbol": book.symbol,
+                "event_time": book.event_time,
+                "bids": levels(&book.bids),
+                "asks": levels(&book.asks),
+            }),
+        );
+    }
+
+    pub fn trade(&self, trade: &MarketTrade) {
+        self.write(
+            "trade",
+            json!({
+                "symbol": trade.symbol,
+                "trade_time": trade.trade_time,
+                "px": trade.px,
+                "qty": trade.qty,
+            }),
+        );
+    }
+
+    pub fn order(&self, event: &OrderEvent) {
+        self.write("order", serde_json::to_value(event).unwrap());
+    }
+
+    pub fn finish(&self) {
+        // writes the gzip trailer, dropping the recorder does it too
+        let mut sink = self.sink.lock().unwrap();
+        sink.encoder
+            .try_finish()
+            .and_then(|_| sink.encoder.get_mut().flush())
+            .expect("couldnt finish the recording");
+        sink.finished = true;
+    }
+
+    pub fn market_data(&self, event: &SimEvent) {
+        match event {
+            SimEvent::Book(book) => self.book(book),
+            SimEvent::Trade(trade) => self.trade(trade),
+        }
+    }
+}
+
+pub fn record(
+    venue: Venue,
+    symbols: &[String],
+    path: &str,
+    seconds: Option<u64>,
+    is_interrupted: impl Fn() -> bool,
+) {
+    // public streams only, nothing is traded. runs for `seconds` or until interrupted
+    let recorder = Recorder::create(path);
+    let rest = Rest::new(venue, Arc::new(RateLimiter::new(venue.rate_limits())));
+    for symbol in symbols.iter() {
+        let rules = rest
+            .symbol_rules(symbol)
+            .expect("couldnt fetch the exchange info");
+        recorder.rules(symbol, &rules);
+    }
+
+    let streams: Vec<String> = symbols
+        .iter()
+        .flat_map(|symbol| {
+            let symbol = symbol.to_lowercase();
+            vec![
+                format!("{}@depth5@100ms", symbol),
+                format!("{}@aggTrade", symbol),
+            ]
+        })
+        .collect();
+    println!("recording streams {:#?}", &streams);
+    let (mut socket, _) = connect(market_data_url(venue, &streams))
+        .expect("couldnt connect to the market data streams");
+
+    let deadline = seconds.map(|seconds| Instant::now() + Duration::from_secs(seconds));
+    while !is_interrupted() && deadline.map_or(true, |deadline| Instant::now() < deadline) {
+        match socket.read().expect("market data stream broke") {
+            Message::Text(text) => {
+                let message: Value = serde_json::from_str(&text).unwrap();
+                let stream = message["stream"].as_str().expect("combined stream name");
+                recorder.raw(stream, &message["data"]);
+                recorder.market_data(&parse_market_data(venue, stream, &message["data"]));
+            }
+            Message::Close(frame) => {
+                dbg!(frame);
+                panic!("market data stream closed");
+            }
+            // pings are answered by tungstenite
+            _ => {}
+        }
+    }
+    recorder.finish();
+    println!("done");
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbols, path, venue = "usdm", seconds = None))]
+pub fn record_market_data(
+    py: Python,
+    symbols: Vec<String>,
+    path: &str,
+    venue: &str,
+    seconds: Option<u64>,
+) -> PyResult<()> {
+    let venue = Venue::parse(venue).map_err(PyValueError::new_err)?;
+    py.allow_threads(|| {
+        record(venue, &symbols, path, seconds, || {
+            Python::with_gil(|py| py.check_signals().is_err())
+        })
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::load_recording;
+
+    #[test]
+    fn test_replay_recording() {
+        let path = std::env::temp_dir().join(format!("recorder-{}.jsonl.gz", std::process::id()));
+        let path = path.to_str().unwrap();
+        let recorder = Recorder::create(path);
+        let rules = SymbolRules {
+            tick_size: 0.1,
+            min_price: 1.0,
+            max_price: 1000000.0,
+            step_size: 0.001,
+            min_qty: 0.001,
+            max_qty: 1000.0,
+            min_notional: 5.0,
+            contract_size: None,
+        };
+        recorder.rules("BTCUSDT", &rules);
+        recorder.raw("btcusdt@depth5@100ms", &json!({"e": "depthUpdate"}));
+        recorder.book(&Book {
+            symbol: "BTCUSDT".to_string(),
+            event_time: 1,
+            bids: vec![Level {
+                px: 100.0,
+                qty: 3.0,
+            }],
+            asks: vec![Level {
+                px: 100.1,
+                qty: 2.0,
+            }],
+        });
+        recorder.trade(&MarketTrade {
+            symbol: "BTCUSDT".to_string(),
+            trade_time: 2,
+            px: 100.1,
+            qty: 0.5,
+        });
+        recorder.order(&OrderEvent {
+            symbol: "BTCUSDT".to_string(),
+            order_id: 1,
+            execution_type: "NEW".to_string(),
+            order_status: "NEW".to_string(),
+            last_filled_qty: 0.0,
+            last_filled_px: 0.0,
+            average_px: 0.0,
+            event_time: 3,
+            transaction_time: 3,
+        });
+        recorder.finish();
+
+        let (recorded_rules, script) = load_recording(path).unwrap();
+        assert_eq!(recorded_rules["BTCUSDT"].tick_size, 0.1);
+        assert_eq!(script.len(), 2);
+        match &script[0] {
+            SimEvent::Book(book) => {
+                assert_eq!(book.event_time, 1);
+                assert_eq!(
+                    book.asks,
+                    vec![Level {
+                        px: 100.1,
+                        qty: 2.0
+                    }]
+                );
+            }
+            SimEvent::Trade(_) => panic!("expected the book first"),
+        }
+        match &script[1] {
+            SimEvent::Trade(trade) => assert_eq!((trade.px, trade.qty), (100.1, 0.5)),
+            SimEvent::Book(_) => panic!("expected the trade second"),
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
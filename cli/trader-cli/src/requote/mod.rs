@@ -0,0 +1,48 @@
+#[derive(Debug, Clone)]
+pub struct RequotePolicy {
+    // requote once the quote has moved this many ticks away from the resting order
+    pub min_ticks: u32,
+    // or once the order has been stale for this long
+    pub delay_ms: Option<u64>,
+    // never cancel an order that has been in the book for less than this
+    pub min_rest_ms: u64,
+}
+
+impl RequotePolicy {
+    pub fn should_requote(&self, drift_ticks: f64, stale_for_ms: u64, rested_for_ms: u64) -> bool {
+        if rested_for_ms < self.min_rest_ms {
+            return false;
+        }
+        // the epsilon absorbs float noise from dividing prices by the tick size
+        let moved_enough = drift_ticks + 1e-9 >= self.min_ticks as f64;
+        let stale_long_enough = self
+            .delay_ms
+            .is_some_and(|delay_ms| stale_for_ms >= delay_ms);
+        moved_enough || stale_long_enough
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_requote() {
+        let policy = RequotePolicy {
+            min_ticks: 0,
+            delay_ms: None,
+            min_rest_ms: 0,
+        };
+        assert!(policy.should_requote(1.0, 0, 0));
+
+        let policy = RequotePolicy {
+            min_ticks: 3,
+            delay_ms: Some(500),
+            min_rest_ms: 1_000,
+        };
+        assert!(!policy.should_requote(5.0, 0, 999));
+        assert!(policy.should_requote(3.0, 0, 1_000));
+        assert!(!policy.should_requote(1.0, 499, 2_000));
+        assert!(policy.should_requote(1.0, 500, 2_000));
+    }
+}
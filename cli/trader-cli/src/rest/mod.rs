@@ -0,0 +1,541 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use binance::errors::{BinanceContentError, Error, ErrorKind};
+use hmac::{Hmac, Mac};
+use reqwest::{blocking::Client, header::HeaderMap, Method, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::Sha256;
+
+use crate::{
+    exchange::{
+        Book, Exchange, ExchangeError, OrderAck, OrderRequest, OrderType, PositionRisk, Side,
+        TimeInForce,
+    },
+    ratelimit::{RateLimiter, RequestKind},
+    rules::SymbolRules,
+    subscriber::{get_config, partial_book, Keys},
+    venue::{normalize_error_code, Venue},
+};
+
+// returned instead of sending when the shared limiter has no headroom, the caller retries next tick
+pub const RATE_LIMITED: &str = "rate limited locally";
+
+pub const UNKNOWN_STATUS: &str = "execution status unknown";
+
+fn is_rate_limited(e: &Error) -> bool {
+    match &e.0 {
+        ErrorKind::Msg(msg) => msg == RATE_LIMITED,
+        ErrorKind::BinanceError(BinanceContentError { code, .. }) => {
+            *code == -1003 || *code == -1015
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PositionMode {
+    dual_side_position: bool,
+}
+
+fn is_unknown_status(e: &Error) -> bool {
+    // timeouts and 5xx, the request may or may not have been executed
+    match &e.0 {
+        ErrorKind::ReqError(_) => true,
+        ErrorKind::Msg(msg) => msg == UNKNOWN_STATUS,
+        ErrorKind::BinanceError(BinanceContentError { code, .. }) => {
+            *code == -1001 || *code == -1007
+        }
+        _ => false,
+    }
+}
+
+impl From<Error> for ExchangeError {
+    fn from(e: Error) -> Self {
+        match e.0 {
+            _ if is_rate_limited(&e) => ExchangeError::RateLimited,
+            _ if is_unknown_status(&e) => ExchangeError::UnknownStatus,
+            ErrorKind::BinanceError(BinanceContentError { code, msg }) => ExchangeError::Rejected {
+                code: code,
+                msg: msg,
+            },
+            _ => ExchangeError::Other(e.to_string()),
+        }
+    }
+}
+
+// signed order endpoints of a venue, binance-rs drops the response headers so the usage counters
+// are read here. modify, batch and position endpoints are futures only
+#[derive(Debug, Clone)]
+pub struct Rest {
+    pub venue: Venue,
+    keys: Keys,
+    base_url: String,
+    recv_window: u64,
+    client: Client,
+    pub limiter: Arc<RateLimiter>,
+}
+
+impl Rest {
+    pub fn new(venue: Venue, limiter: Arc<RateLimiter>) -> Self {
+        let config = get_config();
+        Rest {
+            venue: venue,
+            keys: Keys::new(),
+            base_url: venue.rest_endpoint(&config),
+            recv_window: config.recv_window,
+            client: Client::new(),
+            limiter: limiter,
+        }
+    }
+
+    pub fn symbol_rules(&self, symbol: &str) -> Result<SymbolRules, Error> {
+        // public, binance-rs has no model for the spot NOTIONAL filter
+        let path = self.venue.exchange_info_path();
+        // only spot can filter by symbol, the futures ones return everything
+        let (url, weight) = match self.venue {
+            Venue::Spot => (format!("{}{}?symbol={}", self.base_url, path, symbol), 20),
+            _ => (format!("{}{}", self.base_url, path), 1),
+        };
+        let info: serde_json::Value = self.send(Method::GET, url, RequestKind::Query, weight, 0)?;
+        let info = info["symbols"]
+            .as_array()
+            .and_then(|symbols| symbols.iter().find(|info| info["symbol"] == symbol))
+            .ok_or(Error::from(format!(
+                "{} not found in exchange info",
+                symbol
+            )))?;
+        let mut rules = SymbolRules::from_json_filters(info["filters"].as_array().unwrap());
+        if self.venue == Venue::CoinM {
+            rules.contract_size = Some(info["contractSize"].as_f64().expect("contractSize"));
+        }
+        Ok(rules)
+    }
+
+    pub fn book(&self, symbol: &str) -> Result<Book, Error> {
+        // public, the top 5 levels
+        let url = format!(
+            "{}{}?symbol={}&limit=5",
+            self.base_url,
+            self.venue.depth_path(),
+            symbol
+        );
+        let weight = match self.venue {
+            Venue::Spot => 5,
+            _ => 2,
+        };
+        let depth: serde_json::Value =
+            self.send(Method::GET, url, RequestKind::Query, weight, 0)?;
+        let mut book = partial_book(symbol, &depth);
+        if let Some(event_time) = depth["E"].as_u64() {
+            book.event_time = event_time;
+        }
+        Ok(book)
+    }
+
+    pub fn start_user_stream(&self) -> Result<String, Error> {
+        // creates the listen key or extends the one that is active, only needs the api key
+        let url = format!("{}{}", self.base_url, self.venue.listen_key_path());
+        let user_stream: serde_json::Value =
+            self.send(Method::POST, url, RequestKind::Query, 1, 0)?;
+        Ok(user_stream["listenKey"]
+            .as_str()
+            .ok_or(Error::from("no listenKey"))?
+            .to_string())
+    }
+
+    pub fn is_hedge_mode(&self) -> Result<bool, Error> {
+        let mode: PositionMode = self.request(
+            Method::GET,
+            self.venue.position_mode_path(),
+            vec![],
+            RequestKind::Query,
+            30,
+            0,
+        )?;
+        Ok(mode.dual_side_position)
+    }
+
+    pub fn batch_orders(
+        &self,
+        orders: &[(OrderRequest, String)],
+    ) -> Result<Vec<Result<OrderAck, ExchangeError>>, ExchangeError> {
+        // max 5 orders per call, the responses come back in the same order
+        let batch = orders
+            .iter()
+            .map(|(order, client_order_id)| {
+                order_params(order, client_order_id, self.venue)
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), serde_json::Value::String(value)))
+                    .collect::<serde_json::Map<String, serde_json::Value>>()
+            })
+            .collect::<Vec<_>>();
+        let responses: Vec<serde_json::Value> = self.request(
+            Method::POST,
+            self.venue.batch_orders_path(),
+            vec![("batchOrders", serde_json::to_string(&batch).unwrap())],
+            RequestKind::Order,
+            5,
+            orders.len() as u32,
+        )?;
+        Ok(responses.into_iter().map(batch_response).collect())
+    }
+
+    pub fn batch_cancel(
+        &self,
+        symbol: &str,
+        client_order_ids: &[String],
+    ) -> Result<Vec<Result<(), ExchangeError>>, ExchangeError> {
+        // max 10 orders per call, all for the same symbol
+        let responses: Vec<serde_json::Value> = self.request(
+            Method::DELETE,
+            self.venue.batch_orders_path(),
+            vec![
+                ("symbol", symbol.to_string()),
+                (
+                    "origClientOrderIdList",
+                    serde_json::to_string(client_order_ids).unwrap(),
+                ),
+            ],
+            RequestKind::Cancel,
+            1,
+            0,
+        )?;
+        Ok(responses
+            .into_iter()
+            .map(|response| batch_response::<serde_json::Value>(response).map(|_| ()))
+            .collect())
+    }
+
+    fn sign(&self, params: Vec<(&str, String)>) -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let mut query = params
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{}={}",
+                    key,
+                    url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>()
+                )
+            })
+            .collect::<Vec<String>>();
+        query.push(format!("recvWindow={}", self.recv_window));
+        query.push(format!("timestamp={}", timestamp));
+        let query = query.join("&");
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.keys.secret_key.as_bytes()).unwrap();
+        mac.update(query.as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+        format!("{}&signature={}", query, signature)
+    }
+
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: Vec<(&str, String)>,
+        kind: RequestKind,
+        weight: u32,
+        orders: u32,
+    ) -> Result<T, Error> {
+        let url = format!("{}{}?{}", self.base_url, path, self.sign(params));
+        self.send(method, url, kind, weight, orders)
+    }
+
+    fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: String,
+        kind: RequestKind,
+        weight: u32,
+        orders: u32,
+    ) -> Result<T, Error> {
+        if !self.limiter.try_acquire(kind, weight, orders) {
+            return Err(Error::from(RATE_LIMITED));
+        }
+        let response = self
+            .client
+            .request(method, url)
+            .header("X-MBX-APIKEY", &self.keys.api_key)
+            .send()?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        self.limiter.update_usage(
+            header_u32(&headers, "x-mbx-used-weight-1m"),
+            header_u32(&headers, "x-mbx-order-count-10s"),
+            header_u32(&headers, "x-mbx-order-count-1m"),
+        );
+        let body = response.text()?;
+
+        match status {
+            StatusCode::OK => Ok(serde_json::from_str(&body)?),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::IM_A_TEAPOT => {
+                self.limiter.back_off(
+                    header_u32(&headers, "retry-after").map(|secs| Duration::from_secs(secs as u64)),
+                );
+                Err(Error::from_kind(ErrorKind::BinanceError(BinanceContentError {
+                    code: -1003,
+                    msg: body,
+                })))
+            }
+            _ if status.is_server_error() => match serde_json::from_str(&body) {
+                Ok(content) => Err(Error::from_kind(ErrorKind::BinanceError(content))),
+                Err(_) => Err(Error::from(UNKNOWN_STATUS)),
+            },
+            _ => {
+                let mut content: BinanceContentError = serde_json::from_str(&body)?;
+                content.code = normalize_error_code(self.venue, content.code, &content.msg);
+                if content.code == -1003 || content.code == -1015 {
+                    self.limiter.back_off(None);
+                }
+                Err(Error::from_kind(ErrorKind::BinanceError(content)))
+            }
+        }
+    }
+}
+
+impl Exchange for Rest {
+    fn place_order(
+        &self,
+        order: &OrderRequest,
+        client_order_id: &str,
+    ) -> Result<OrderAck, ExchangeError> {
+        Ok(self.request(
+            Method::POST,
+            self.venue.order_path(),
+            order_params(order, client_order_id, self.venue),
+            RequestKind::Order,
+            1,
+            1,
+        )?)
+    }
+
+    fn test_order(&self, order: &OrderRequest, client_order_id: &str) -> Result<(), ExchangeError> {
+        // the same parameters as place_order, binance answers {} or the error the order would get
+        let path = self
+            .venue
+            .order_test_path()
+            .ok_or(ExchangeError::Other(format!(
+                "{} has no test order endpoint",
+                self.venue.as_str()
+            )))?;
+        let _: serde_json::Value = self.request(
+            Method::POST,
+            path,
+            order_params(order, client_order_id, self.venue),
+            RequestKind::Query,
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    fn modify_order(
+        &self,
+        symbol: &str,
+        client_order_id: &str,
+        side: Side,
+        qty: f64,
+        price: f64,
+    ) -> Result<OrderAck, ExchangeError> {
+        // counts towards the order limits like a new order
+        Ok(self.request(
+            Method::PUT,
+            self.venue.order_path(),
+            vec![
+                ("symbol", symbol.to_string()),
+                ("origClientOrderId", client_order_id.to_string()),
+                ("side", side.as_str().to_string()),
+                ("quantity", qty.to_string()),
+                ("price", price.to_string()),
+            ],
+            RequestKind::Order,
+            1,
+            1,
+        )?)
+    }
+
+    fn cancel_order(&self, symbol: &str, client_order_id: &str) -> Result<(), ExchangeError> {
+        let _: serde_json::Value = self.request(
+            Method::DELETE,
+            self.venue.order_path(),
+            vec![
+                ("symbol", symbol.to_string()),
+                ("origClientOrderId", client_order_id.to_string()),
+            ],
+            RequestKind::Cancel,
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    fn open_orders(&self, symbol: &str) -> Result<Vec<OrderAck>, ExchangeError> {
+        let weight = match self.venue {
+            Venue::UsdM | Venue::CoinM => 1,
+            Venue::Spot => 6,
+        };
+        Ok(self.request(
+            Method::GET,
+            self.venue.open_orders_path(),
+            vec![("symbol", symbol.to_string())],
+            RequestKind::Query,
+            weight,
+            0,
+        )?)
+    }
+
+    fn position_risk(&self, symbol: &str) -> Result<Vec<PositionRisk>, ExchangeError> {
+        let (params, weight) = match self.venue {
+            // by pair on coin-m, e.g. BTCUSD for BTCUSD_PERP and the quarterlies
+            Venue::CoinM => {
+                let pair = symbol.split('_').next().unwrap_or(symbol);
+                (vec![("pair", pair.to_string())], 1)
+            }
+            _ => (vec![("symbol", symbol.to_string())], 5),
+        };
+        let risks: Vec<PositionRisk> = self.request(
+            Method::GET,
+            self.venue.position_risk_path(),
+            params,
+            RequestKind::Query,
+            weight,
+            0,
+        )?;
+        Ok(risks
+            .into_iter()
+            .filter(|risk| risk.symbol == symbol)
+            .collect())
+    }
+
+    fn query_order(&self, symbol: &str, client_order_id: &str) -> Result<OrderAck, ExchangeError> {
+        let weight = match self.venue {
+            Venue::UsdM | Venue::CoinM => 1,
+            Venue::Spot => 4,
+        };
+        Ok(self.request(
+            Method::GET,
+            self.venue.order_path(),
+            vec![
+                ("symbol", symbol.to_string()),
+                ("origClientOrderId", client_order_id.to_string()),
+            ],
+            RequestKind::Query,
+            weight,
+            0,
+        )?)
+    }
+}
+
+fn batch_response<T: DeserializeOwned>(response: serde_json::Value) -> Result<T, ExchangeError> {
+    // each entry is either the order or {"code": .., "msg": ..}
+    match response.get("code").is_some() && response.get("orderId").is_none() {
+        true => {
+            let content: BinanceContentError = serde_json::from_value(response).unwrap();
+            Err(ExchangeError::from(Error::from_kind(
+                ErrorKind::BinanceError(content),
+            )))
+        }
+        false => serde_json::from_value(response).map_err(|e| ExchangeError::Other(e.to_string())),
+    }
+}
+
+fn header_u32(headers: &HeaderMap, name: &str) -> Option<u32> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u32>().ok())
+}
+
+fn order_params(
+    order: &OrderRequest,
+    client_order_id: &str,
+    venue: Venue,
+) -> Vec<(&'static str, String)> {
+    let (order_type, time_in_force) = match (order.order_type, order.time_in_force, venue) {
+        // spot has no GTX, post-only is its own order type
+        (OrderType::Limit, Some(TimeInForce::PostOnly), Venue::Spot) => ("LIMIT_MAKER", None),
+        (OrderType::Limit, Some(TimeInForce::PostOnly), _) => ("LIMIT", Some("GTX")),
+        (OrderType::Limit, Some(TimeInForce::Ioc), _) => ("LIMIT", Some("IOC")),
+        (OrderType::Limit, None, _) => ("LIMIT", Some("GTC")),
+        (OrderType::Market, _, _) => ("MARKET", None),
+    };
+    let mut params = vec![
+        ("symbol", order.symbol.to_string()),
+        ("newClientOrderId", client_order_id.to_string()),
+        ("side", order.side.as_str().to_string()),
+        ("type", order_type.to_string()),
+    ];
+    if let Some(time_in_force) = time_in_force {
+        params.push(("timeInForce", time_in_force.to_string()));
+    }
+    if let Some(position_side) = &order.position_side {
+        params.push(("positionSide", position_side.to_string()));
+    }
+    params.push(("quantity", order.qty.to_string()));
+    if let Some(price) = order.price {
+        params.push(("price", price.to_string()));
+    }
+    if let Some(reduce_only) = order.reduce_only {
+        params.push(("reduceOnly", reduce_only.to_string().to_uppercase()));
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_response() {
+        let rejected = serde_json::json!({"code": -2022, "msg": "ReduceOnly Order is rejected."});
+        match batch_response::<serde_json::Value>(rejected) {
+            Err(ExchangeError::Rejected { code, .. }) => assert_eq!(code, -2022),
+            _ => panic!("should be rejected"),
+        }
+        let placed = serde_json::json!({"orderId": 1, "symbol": "BTCUSDT", "status": "NEW"});
+        assert!(batch_response::<serde_json::Value>(placed).is_ok());
+        let placed = serde_json::json!({
+            "orderId": 1,
+            "clientOrderId": "rt-1f3a9c2e-0-1",
+            "origQty": "0.010",
+            "executedQty": "0",
+            "price": "63317.20",
+            "side": "BUY",
+            "type": "LIMIT",
+            "reduceOnly": false,
+            "positionSide": "LONG",
+            "timeInForce": "GTX",
+            "status": "NEW",
+            "updateTime": 1727171378370_u64
+        });
+        let ack = batch_response::<OrderAck>(placed).unwrap();
+        assert_eq!(ack.orig_qty, 0.01);
+        assert_eq!(ack.position_side, "LONG");
+    }
+
+    #[test]
+    fn test_spot_post_only() {
+        let order = OrderRequest {
+            symbol: "BTCUSDT".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            time_in_force: Some(TimeInForce::PostOnly),
+            qty: 0.002,
+            price: Some(63000.0),
+            reduce_only: None,
+            position_side: None,
+        };
+        let params = order_params(&order, "rt-1f3a9c2e-0-1", Venue::Spot);
+        assert!(params.contains(&("type", "LIMIT_MAKER".to_string())));
+        assert!(!params.iter().any(|(key, _)| *key == "timeInForce"));
+        let params = order_params(&order, "rt-1f3a9c2e-0-1", Venue::UsdM);
+        assert!(params.contains(&("timeInForce", "GTX".to_string())));
+    }
+}
@@ -0,0 +1,261 @@
+use binance::{futures::model::Symbol, model::Filters};
+
+use crate::utils::Round;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleViolation {
+    PriceOutOfRange { px: f64 },
+    QtyBelowMin { qty: f64 },
+    NotionalBelowMin { notional: f64 },
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct SymbolRules {
+    pub tick_size: f64,
+    pub min_price: f64,
+    pub max_price: f64,
+    pub step_size: f64,
+    pub min_qty: f64,
+    pub max_qty: f64,
+    pub min_notional: f64,
+    // coin-m contracts are worth a fixed amount of quote currency, None for base qty venues
+    pub contract_size: Option<f64>,
+}
+
+fn parse_filter_value(value: &str) -> f64 {
+    value
+        .parse::<f64>()
+        .expect("parsable string. wont fail unless binance breaks it")
+}
+
+impl SymbolRules {
+    pub fn from_filters(filters: &[Filters]) -> Self {
+        let mut rules = SymbolRules {
+            tick_size: 0.0,
+            min_price: 0.0,
+            max_price: 0.0,
+            step_size: 0.0,
+            min_qty: 0.0,
+            max_qty: 0.0,
+            min_notional: 0.0,
+            contract_size: None,
+        };
+        for filter in filters {
+            match filter {
+                Filters::PriceFilter {
+                    min_price,
+                    max_price,
+                    tick_size,
+                } => {
+                    rules.min_price = parse_filter_value(min_price);
+                    rules.max_price = parse_filter_value(max_price);
+                    rules.tick_size = parse_filter_value(tick_size);
+                }
+                Filters::LotSize {
+                    min_qty,
+                    max_qty,
+                    step_size,
+                } => {
+                    rules.min_qty = parse_filter_value(min_qty);
+                    rules.max_qty = parse_filter_value(max_qty);
+                    rules.step_size = parse_filter_value(step_size);
+                }
+                Filters::MinNotional { notional, .. } => {
+                    rules.min_notional = parse_filter_value(notional.as_ref().unwrap());
+                }
+                _ => {}
+            }
+        }
+        assert!(
+            rules.tick_size > 0.0 && rules.step_size > 0.0,
+            "PRICE_FILTER and LOT_SIZE are required"
+        );
+        rules
+    }
+
+    pub fn from_json_filters(filters: &[serde_json::Value]) -> Self {
+        // the raw exchangeInfo filters, spot has NOTIONAL filters binance-rs doesn't model
+        let value = |filter: &serde_json::Value, key: &str| {
+            filter
+                .get(key)
+                .and_then(|value| value.as_str())
+                .map(parse_filter_value)
+                .unwrap_or(0.0)
+        };
+        let mut rules = SymbolRules {
+            tick_size: 0.0,
+            min_price: 0.0,
+            max_price: 0.0,
+            step_size: 0.0,
+            min_qty: 0.0,
+            max_qty: 0.0,
+            min_notional: 0.0,
+            contract_size: None,
+        };
+        for filter in filters {
+            match filter.get("filterType").and_then(|value| value.as_str()) {
+                Some("PRICE_FILTER") => {
+                    rules.min_price = value(filter, "minPrice");
+                    rules.max_price = value(filter, "maxPrice");
+                    rules.tick_size = value(filter, "tickSize");
+                }
+                Some("LOT_SIZE") => {
+                    rules.min_qty = value(filter, "minQty");
+                    rules.max_qty = value(filter, "maxQty");
+                    rules.step_size = value(filter, "stepSize");
+                }
+                // spot names it minNotional, futures notional
+                Some("NOTIONAL") | Some("MIN_NOTIONAL") => {
+                    rules.min_notional =
+                        value(filter, "minNotional").max(value(filter, "notional"));
+                }
+                _ => {}
+            }
+        }
+        assert!(
+            rules.tick_size > 0.0 && rules.step_size > 0.0,
+            "PRICE_FILTER and LOT_SIZE are required"
+        );
+        rules
+    }
+
+    pub fn notional(&self, px: f64, qty: f64) -> f64 {
+        match self.contract_size {
+            Some(contract_size) => qty * contract_size,
+            None => px * qty,
+        }
+    }
+
+    pub fn qty_for_notional(&self, notional: f64, px: f64) -> f64 {
+        match self.contract_size {
+            Some(contract_size) => notional / contract_size,
+            None => notional / px,
+        }
+    }
+
+    pub fn round_price(&self, px: f64, is_long: bool) -> f64 {
+        // snap to the passive side so rounding can never make the order marketable
+        match is_long {
+            true => px.floor_to_step(self.tick_size),
+            false => px.ceil_to_step(self.tick_size),
+        }
+    }
+
+    pub fn round_qty(&self, qty: f64) -> f64 {
+        // floors toward zero and caps at maxQty, keeps the sign
+        let sz = match self.max_qty > 0.0 {
+            true => qty.abs().min(self.max_qty),
+            false => qty.abs(),
+        };
+        sz.floor_to_step(self.step_size) * qty.signum()
+    }
+
+    pub fn validate(&self, px: f64, qty: f64, reduce_only: bool) -> Result<(), RuleViolation> {
+        // a max of 0 means the filter is not enforced
+        if px < self.min_price || (self.max_price > 0.0 && px > self.max_price) {
+            return Err(RuleViolation::PriceOutOfRange { px: px });
+        }
+        if qty.abs() < self.min_qty || qty == 0.0 {
+            return Err(RuleViolation::QtyBelowMin { qty: qty });
+        }
+        let notional = self.notional(px, qty).abs();
+        if !reduce_only && notional < self.min_notional {
+            return Err(RuleViolation::NotionalBelowMin { notional: notional });
+        }
+        Ok(())
+    }
+}
+
+impl From<&Symbol> for SymbolRules {
+    fn from(symbol: &Symbol) -> Self {
+        SymbolRules::from_filters(&symbol.filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn btcusdt() -> SymbolRules {
+        SymbolRules {
+            tick_size: 0.1,
+            min_price: 556.8,
+            max_price: 4529764.0,
+            step_size: 0.001,
+            min_qty: 0.001,
+            max_qty: 1000.0,
+            min_notional: 100.0,
+            contract_size: None,
+        }
+    }
+
+    #[test]
+    fn test_round_price_to_passive_side() {
+        let rules = btcusdt();
+        assert_eq!(rules.round_price(65000.07, true), 65000.0);
+        assert_eq!(rules.round_price(65000.07, false), 65000.1);
+        assert_eq!(rules.round_price(65000.1, true), 65000.1);
+    }
+
+    #[test]
+    fn test_round_qty_floors_toward_zero() {
+        let rules = btcusdt();
+        assert_eq!(rules.round_qty(0.0159), 0.015);
+        assert_eq!(rules.round_qty(-0.0159), -0.015);
+        assert_eq!(rules.round_qty(0.3), 0.3);
+        assert_eq!(rules.round_qty(1500.0), 1000.0);
+    }
+
+    #[test]
+    fn test_validate() {
+        let rules = btcusdt();
+        assert_eq!(rules.validate(65000.0, 0.01, false), Ok(()));
+        assert!(matches!(
+            rules.validate(65000.0, 0.0, false),
+            Err(RuleViolation::QtyBelowMin { .. })
+        ));
+        assert!(matches!(
+            rules.validate(65000.0, 0.001, false),
+            Err(RuleViolation::NotionalBelowMin { .. })
+        ));
+        assert_eq!(rules.validate(65000.0, 0.001, true), Ok(()));
+        assert!(matches!(
+            rules.validate(100.0, 0.01, false),
+            Err(RuleViolation::PriceOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_spot_filters() {
+        let filters = serde_json::json!([
+            {"filterType": "PRICE_FILTER", "minPrice": "0.01000000", "maxPrice": "1000000.00000000", "tickSize": "0.01000000"},
+            {"filterType": "LOT_SIZE", "minQty": "0.00001000", "maxQty": "9000.00000000", "stepSize": "0.00001000"},
+            {"filterType": "NOTIONAL", "minNotional": "5.00000000", "applyMinToMarket": true, "maxNotional": "9000000.00000000"}
+        ]);
+        let rules = SymbolRules::from_json_filters(filters.as_array().unwrap());
+        assert_eq!(rules.tick_size, 0.01);
+        assert_eq!(rules.step_size, 0.00001);
+        assert_eq!(rules.min_notional, 5.0);
+    }
+
+    #[test]
+    fn test_contract_sizing() {
+        let rules = SymbolRules {
+            tick_size: 0.1,
+            min_price: 1000.0,
+            max_price: 2000000.0,
+            step_size: 1.0,
+            min_qty: 1.0,
+            max_qty: 10000.0,
+            min_notional: 0.0,
+            contract_size: Some(100.0),
+        };
+        // 1000 usd of BTCUSD_PERP is 10 contracts whatever the price
+        assert_eq!(rules.qty_for_notional(1000.0, 65000.0), 10.0);
+        assert_eq!(rules.notional(65000.0, 10.0), 1000.0);
+        assert_eq!(
+            rules.round_qty(rules.qty_for_notional(1050.0, 65000.0)),
+            10.0
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Urgency {
+    Passive,
+    Improve,
+    Sweep,
+}
+
+impl Urgency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Urgency::Passive => "passive",
+            Urgency::Improve => "improve",
+            Urgency::Sweep => "sweep",
+        }
+    }
+}
+
+fn secs_to_ms(secs: f64) -> u64 {
+    (secs * 1000.0) as u64
+}
+
+#[derive(Debug, Clone)]
+pub struct UrgencySchedule {
+    // all timestamps are exchange event times in ms
+    pub escalate_at: Option<u64>,
+    pub deadline: Option<u64>,
+}
+
+impl UrgencySchedule {
+    pub fn new(
+        started_at: u64,
+        escalate_after_secs: Option<f64>,
+        deadline_secs: Option<f64>,
+    ) -> Self {
+        // without an explicit escalation time we start improving halfway to the deadline
+        let escalate_after_secs = escalate_after_secs.or(deadline_secs.map(|x| x / 2.0));
+        UrgencySchedule {
+            escalate_at: escalate_after_secs.map(|x| started_at + secs_to_ms(x)),
+            deadline: deadline_secs.map(|x| started_at + secs_to_ms(x)),
+        }
+    }
+
+    pub fn urgency_at(&self, ts: u64) -> Urgency {
+        if self.deadline.is_some_and(|deadline| ts >= deadline) {
+            return Urgency::Sweep;
+        }
+        if self
+            .escalate_at
+            .is_some_and(|escalate_at| ts >= escalate_at)
+        {
+            return Urgency::Improve;
+        }
+        Urgency::Passive
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TwapSchedule {
+    // release time of each slice, the first one is released immediately
+    pub slice_times: Vec<u64>,
+}
+
+impl TwapSchedule {
+    pub fn new(started_at: u64, duration_secs: f64, slices: u32, jitter: f64) -> Self {
+        // jitter is a fraction of the slice interval, each release moves by up to half of it either way
+        let slices = slices.max(1);
+        let interval = secs_to_ms(duration_secs) as f64 / slices as f64;
+        let max_offset = interval * jitter.clamp(0.0, 1.0) / 2.0;
+        let mut rng = rand::thread_rng();
+        let slice_times = (0..slices)
+            .map(|i| match (i, max_offset > 0.0) {
+                (0, _) | (_, false) => started_at + (interval * i as f64) as u64,
+                (_, true) => {
+                    let offset = rng.gen_range(-max_offset..max_offset);
+                    started_at + (interval * i as f64 + offset) as u64
+                }
+            })
+            .collect();
+        TwapSchedule {
+            slice_times: slice_times,
+        }
+    }
+
+    pub fn released_fraction(&self, ts: u64) -> f64 {
+        let released = self.slice_times.iter().filter(|x| **x <= ts).count();
+        released as f64 / self.slice_times.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urgency_schedule() {
+        let schedule = UrgencySchedule::new(1_000, None, Some(60.0));
+        assert_eq!(schedule.urgency_at(1_000), Urgency::Passive);
+        assert_eq!(schedule.urgency_at(30_999), Urgency::Passive);
+        assert_eq!(schedule.urgency_at(31_000), Urgency::Improve);
+        assert_eq!(schedule.urgency_at(61_000), Urgency::Sweep);
+
+        let schedule = UrgencySchedule::new(1_000, Some(10.0), None);
+        assert_eq!(schedule.urgency_at(11_000), Urgency::Improve);
+        assert_eq!(schedule.urgency_at(u64::MAX), Urgency::Improve);
+
+        let schedule = UrgencySchedule::new(1_000, None, None);
+        assert_eq!(schedule.urgency_at(u64::MAX), Urgency::Passive);
+    }
+
+    #[test]
+    fn test_twap_releases_slices() {
+        let twap = TwapSchedule::new(0, 60.0, 4, 0.0);
+        assert_eq!(twap.slice_times, vec![0, 15_000, 30_000, 45_000]);
+        assert_eq!(twap.released_fraction(0), 0.25);
+        assert_eq!(twap.released_fraction(29_999), 0.5);
+        assert_eq!(twap.released_fraction(60_000), 1.0);
+
+        let twap = TwapSchedule::new(0, 60.0, 4, 1.0);
+        assert_eq!(twap.slice_times[0], 0);
+        for (i, slice_time) in twap.slice_times.iter().enumerate().skip(1) {
+            let expected = 15_000 * i as u64;
+            assert!(*slice_time >= expected - 7_500 && *slice_time <= expected + 7_500);
+        }
+    }
+}
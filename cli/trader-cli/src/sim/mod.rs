@@ -0,0 +1,1007 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+use crate::{
+    exchange::{
+        Book, Exchange, ExchangeError, MarketTrade, OrderAck, OrderEvent, OrderRequest, OrderType,
+        PositionRisk, Side, TimeInForce,
+    },
+    ids::{new_run_id, ClientOrderIds},
+    position::Position,
+    reconcile::LeftoverAction,
+    recorder::Recorder,
+    rules::SymbolRules,
+    trader::{SymbolTrader, Trader, TraderSummary},
+};
+
+// -----
+// an in-process exchange for offline runs. orders rest in a queue behind the size shown at their
+// price and fill against the scripted books and trades fed through on_book / on_trade:
+//  - a resting order fills completely once the opposite touch crosses its price
+//  - a print at its price eats the queue ahead first, a print through its price fills it
+// execution reports are queued and handed to the trader by the driver, like the user stream would.
+// with a latency in the FillModel requests only reach the book at the first market event after it
+// -----
+
+#[derive(Debug, Clone, Copy)]
+pub struct FillModel {
+    // one way, our requests reach the exchange this much after the market event that triggered them
+    pub latency_ms: u64,
+    // 0..1, where in the size shown at the price a new order joins. 1 is the back of the queue
+    pub queue_fraction: f64,
+}
+
+impl Default for FillModel {
+    fn default() -> Self {
+        FillModel {
+            latency_ms: 0,
+            queue_fraction: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SimOrder {
+    ack: OrderAck,
+    request: OrderRequest,
+    // not in the book until the request arrived
+    active: bool,
+    // size at the order's price that has to trade before it
+    queue_ahead: f64,
+    cumulative_quote: f64,
+}
+
+impl SimOrder {
+    fn is_open(&self) -> bool {
+        self.ack.status == "NEW" || self.ack.status == "PARTIALLY_FILLED"
+    }
+
+    fn is_buy(&self) -> bool {
+        self.request.side == Side::Buy
+    }
+
+    fn remaining(&self) -> f64 {
+        self.ack.orig_qty - self.ack.executed_qty
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Pending {
+    Activate(String),
+    Modify(String, f64, f64),
+    Cancel(String),
+}
+
+#[derive(Debug, Default)]
+struct SimState {
+    books: HashMap<String, Book>,
+    // by client order id, done orders are kept for query_order
+    orders: HashMap<String, SimOrder>,
+    positions: HashMap<String, f64>,
+    events: Vec<OrderEvent>,
+    // requests still on their way, with the time they arrive
+    pending: Vec<(u64, Pending)>,
+    // returned by the next requests instead of executing them
+    failures: Vec<ExchangeError>,
+    next_order_id: u64,
+    now: u64,
+}
+
+impl SimState {
+    fn level_qty(&self, symbol: &str, is_buy: bool, px: f64) -> f64 {
+        let book = match self.books.get(symbol) {
+            Some(book) => book,
+            None => return 0.0,
+        };
+        let levels = match is_buy {
+            true => &book.bids,
+            false => &book.asks,
+        };
+        levels
+            .iter()
+            .find(|level| level.px == px)
+            .map(|level| level.qty)
+            .unwrap_or(0.0)
+    }
+
+    fn crosses(&self, symbol: &str, is_buy: bool, px: f64) -> bool {
+        let book = &self.books[symbol];
+        match is_buy {
+            true => px >= book.best_ask(),
+            false => px <= book.best_bid(),
+        }
+    }
+
+    fn event(&mut self, client_order_id: &str, execution_type: &str, last_fill: (f64, f64)) {
+        let order = &self.orders[client_order_id];
+        let average_px = match order.ack.executed_qty > 0.0 {
+            true => order.cumulative_quote / order.ack.executed_qty,
+            false => 0.0,
+        };
+        let event = OrderEvent {
+            symbol: order.request.symbol.to_string(),
+            order_id: order.ack.order_id,
+            execution_type: execution_type.to_string(),
+            order_status: order.ack.status.to_string(),
+            last_filled_qty: last_fill.0,
+            last_filled_px: last_fill.1,
+            average_px: average_px,
+            event_time: self.now,
+            transaction_time: self.now,
+        };
+        self.events.push(event);
+    }
+
+    fn fill(&mut self, client_order_id: &str, qty: f64, px: f64) {
+        let order = self.orders.get_mut(client_order_id).unwrap();
+        let qty = qty.min(order.remaining());
+        if qty <= 0.0 {
+            return;
+        }
+        order.ack.executed_qty += qty;
+        order.cumulative_quote += qty * px;
+        order.ack.update_time = self.now;
+        order.ack.status = match order.remaining() > 1e-12 {
+            true => "PARTIALLY_FILLED".to_string(),
+            false => "FILLED".to_string(),
+        };
+        let signed_qty = match order.is_buy() {
+            true => qty,
+            false => -qty,
+        };
+        let symbol = order.request.symbol.to_string();
+        *self.positions.entry(symbol).or_insert(0.0) += signed_qty;
+        self.event(client_order_id, "TRADE", (qty, px));
+    }
+
+    fn take_liquidity(&mut self, client_order_id: &str, limit_px: Option<f64>) {
+        // walks the opposite side of the book up to the limit, the size taken is gone from it
+        let (symbol, is_buy) = {
+            let order = &self.orders[client_order_id];
+            (order.request.symbol.to_string(), order.is_buy())
+        };
+        let mut fills: Vec<(f64, f64)> = vec![];
+        let mut remaining = self.orders[client_order_id].remaining();
+        if let Some(book) = self.books.get_mut(&symbol) {
+            let levels = match is_buy {
+                true => &mut book.asks,
+                false => &mut book.bids,
+            };
+            for level in levels.iter_mut() {
+                let marketable = match (limit_px, is_buy) {
+                    (None, _) => true,
+                    (Some(px), true) => level.px <= px,
+                    (Some(px), false) => level.px >= px,
+                };
+                if remaining <= 0.0 || !marketable {
+                    break;
+                }
+                let qty = remaining.min(level.qty);
+                level.qty -= qty;
+                remaining -= qty;
+                fills.push((qty, level.px));
+            }
+            levels.retain(|level| level.qty > 0.0);
+        }
+        for (qty, px) in fills {
+            self.fill(client_order_id, qty, px);
+        }
+    }
+
+    fn open_orders(&self, symbol: &str) -> Vec<String> {
+        let mut client_order_ids: Vec<(u64, String)> = self
+            .orders
+            .iter()
+            .filter(|(_, order)| order.request.symbol == symbol && order.active && order.is_open())
+            .map(|(client_order_id, order)| (order.ack.order_id, client_order_id.to_string()))
+            .collect();
+        // oldest first, the queue priority
+        client_order_ids.sort();
+        client_order_ids
+            .into_iter()
+            .map(|(_, client_order_id)| client_order_id)
+            .collect()
+    }
+
+    fn activate(
+        &mut self,
+        client_order_id: &str,
+        queue_fraction: f64,
+    ) -> Result<(), ExchangeError> {
+        // the order reaches the book, checked against the book as it is now
+        let request = self.orders[client_order_id].request.clone();
+        let is_buy = request.side == Side::Buy;
+        let crosses = match request.price {
+            Some(px) => self.crosses(&request.symbol, is_buy, px),
+            None => true,
+        };
+        if request.time_in_force == Some(TimeInForce::PostOnly) && crosses {
+            return Err(rejected(
+                -5022,
+                "Due to the order could not be executed as maker, the Post Only order will be rejected.",
+            ));
+        }
+        let queue_ahead = match request.price {
+            Some(px) => self.level_qty(&request.symbol, is_buy, px) * queue_fraction,
+            None => 0.0,
+        };
+        let order = self.orders.get_mut(client_order_id).unwrap();
+        order.active = true;
+        order.queue_ahead = queue_ahead;
+        self.event(client_order_id, "NEW", (0.0, 0.0));
+
+        if crosses {
+            self.take_liquidity(client_order_id, request.price);
+        }
+        // whatever an IOC or market order couldn't take is gone
+        let taker_only = request.order_type == OrderType::Market
+            || request.time_in_force == Some(TimeInForce::Ioc);
+        if taker_only && self.orders[client_order_id].is_open() {
+            self.orders.get_mut(client_order_id).unwrap().ack.status = "EXPIRED".to_string();
+            self.event(client_order_id, "EXPIRED", (0.0, 0.0));
+        }
+        Ok(())
+    }
+
+    fn modify(
+        &mut self,
+        client_order_id: &str,
+        qty: f64,
+        px: f64,
+        queue_fraction: f64,
+    ) -> Result<OrderAck, ExchangeError> {
+        let order = match self.orders.get(client_order_id) {
+            Some(order) if order.is_open() => order.clone(),
+            _ => return Err(rejected(-2013, "Order does not exist.")),
+        };
+        if order.ack.orig_qty == qty && order.ack.price == px {
+            return Err(rejected(-5027, "No need to modify the order."));
+        }
+        if qty <= order.ack.executed_qty {
+            return Err(rejected(-4003, "Quantity less than or equal to zero."));
+        }
+        let symbol = &order.request.symbol;
+        if order.ack.time_in_force == "GTX" && self.crosses(symbol, order.is_buy(), px) {
+            return Err(rejected(
+                -5022,
+                "Due to the order could not be executed as maker, the Post Only order will be rejected.",
+            ));
+        }
+        // a new price loses the queue position
+        let queue_ahead = match px == order.ack.price {
+            true => order.queue_ahead,
+            false => self.level_qty(symbol, order.is_buy(), px) * queue_fraction,
+        };
+        let now = self.now;
+        let order = self.orders.get_mut(client_order_id).unwrap();
+        order.ack.orig_qty = qty;
+        order.ack.price = px;
+        order.ack.update_time = now;
+        order.request.qty = qty;
+        order.request.price = Some(px);
+        order.queue_ahead = queue_ahead;
+        let ack = order.ack.clone();
+        self.event(client_order_id, "AMENDMENT", (0.0, 0.0));
+        Ok(ack)
+    }
+
+    fn cancel(&mut self, client_order_id: &str) -> Result<(), ExchangeError> {
+        match self.orders.get_mut(client_order_id) {
+            Some(order) if order.is_open() => {
+                order.ack.status = "CANCELED".to_string();
+            }
+            _ => return Err(rejected(-2011, "Unknown order sent.")),
+        }
+        self.event(client_order_id, "CANCELED", (0.0, 0.0));
+        Ok(())
+    }
+
+    fn arrive(&mut self, until: u64, queue_fraction: f64) {
+        // the requests sent before `until` are executed in the order they were sent. what the
+        // response would have said reaches the trader through the events, as on the user stream
+        let due = self
+            .pending
+            .iter()
+            .take_while(|(at, _)| *at <= until)
+            .count();
+        let arrived: Vec<(u64, Pending)> = self.pending.drain(..due).collect();
+        for (at, request) in arrived {
+            self.now = self.now.max(at);
+            match request {
+                Pending::Activate(client_order_id) => {
+                    if self.activate(&client_order_id, queue_fraction).is_err() {
+                        // a post-only order that would take expires like on the user stream
+                        let order = self.orders.get_mut(&client_order_id).unwrap();
+                        order.ack.status = "EXPIRED".to_string();
+                        self.event(&client_order_id, "EXPIRED", (0.0, 0.0));
+                    }
+                }
+                // a rejected modify leaves the order as it was
+                Pending::Modify(client_order_id, qty, px) => {
+                    let _ = self.modify(&client_order_id, qty, px, queue_fraction);
+                }
+                // the order filled on the way
+                Pending::Cancel(client_order_id) => {
+                    let _ = self.cancel(&client_order_id);
+                }
+            }
+        }
+    }
+}
+
+fn rejected(code: i16, msg: &str) -> ExchangeError {
+    ExchangeError::Rejected {
+        code: code,
+        msg: msg.to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub struct SimExchange {
+    rules: HashMap<String, SymbolRules>,
+    fill_model: FillModel,
+    state: Mutex<SimState>,
+}
+
+impl SimExchange {
+    pub fn new(rules: HashMap<String, SymbolRules>, fill_model: FillModel) -> Self {
+        SimExchange {
+            rules: rules,
+            fill_model: fill_model,
+            state: Mutex::new(SimState::default()),
+        }
+    }
+
+    pub fn rules(&self, symbol: &str) -> SymbolRules {
+        self.rules
+            .get(symbol)
+            .expect(&format!(
+                "{} is not listed on the simulated exchange",
+                symbol
+            ))
+            .clone()
+    }
+
+    #[cfg(test)]
+    pub fn set_position(&self, symbol: &str, qty: f64) {
+        self.state
+            .lock()
+            .unwrap()
+            .positions
+            .insert(symbol.to_string(), qty);
+    }
+
+    #[cfg(test)]
+    pub fn position(&self, symbol: &str) -> f64 {
+        let state = self.state.lock().unwrap();
+        state.positions.get(symbol).copied().unwrap_or(0.0)
+    }
+
+    pub fn has_book(&self, symbol: &str) -> bool {
+        self.state.lock().unwrap().books.contains_key(symbol)
+    }
+
+    #[cfg(test)]
+    // the next request gets this instead of being executed, e.g. RateLimited or UnknownStatus
+    pub fn fail_next(&self, error: ExchangeError) {
+        self.state.lock().unwrap().failures.push(error);
+    }
+
+    // execution reports since the last call, in the order they happened
+    pub fn take_events(&self) -> Vec<OrderEvent> {
+        self.state.lock().unwrap().events.drain(..).collect()
+    }
+
+    // lets every request still on its way arrive, at the end of a run
+    pub fn flush(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.arrive(u64::MAX, self.fill_model.queue_fraction);
+    }
+
+    pub fn on_book(&self, book: &Book) {
+        let mut state = self.state.lock().unwrap();
+        state.arrive(book.event_time, self.fill_model.queue_fraction);
+        state.now = state.now.max(book.event_time);
+        state.books.insert(book.symbol.to_string(), book.clone());
+        for client_order_id in state.open_orders(&book.symbol) {
+            let order = &state.orders[&client_order_id];
+            let (is_buy, px, remaining) = (order.is_buy(), order.ack.price, order.remaining());
+            match state.crosses(&book.symbol, is_buy, px) {
+                true => state.fill(&client_order_id, remaining, px),
+                // the queue can't be longer than what is left at the price
+                false => {
+                    let level_qty = state.level_qty(&book.symbol, is_buy, px);
+                    let order = state.orders.get_mut(&client_order_id).unwrap();
+                    order.queue_ahead = order.queue_ahead.min(level_qty);
+                }
+            }
+        }
+    }
+
+    pub fn on_trade(&self, trade: &MarketTrade) {
+        let mut state = self.state.lock().unwrap();
+        state.arrive(trade.trade_time, self.fill_model.queue_fraction);
+        state.now = state.now.max(trade.trade_time);
+        // what is left of the print after the queue and our earlier orders at the price
+        let mut traded = trade.qty;
+        for client_order_id in state.open_orders(&trade.symbol) {
+            let order = state.orders.get_mut(&client_order_id).unwrap();
+            let (is_buy, px) = (order.is_buy(), order.ack.price);
+            let through = match is_buy {
+                true => trade.px < px,
+                false => trade.px > px,
+            };
+            let fill_qty = match (through, trade.px == px) {
+                (true, _) => order.remaining(),
+                (false, true) => {
+                    let queue_taken = order.queue_ahead.min(traded);
+                    order.queue_ahead -= queue_taken;
+                    traded -= queue_taken;
+                    let fill_qty = traded.min(order.remaining());
+                    traded -= fill_qty;
+                    fill_qty
+                }
+                (false, false) => 0.0,
+            };
+            state.fill(&client_order_id, fill_qty, px);
+        }
+    }
+
+    fn send(&self, state: &mut SimState, request: Pending) {
+        let at = state.now + self.fill_model.latency_ms;
+        state.pending.push((at, request));
+    }
+
+    fn check_order(&self, state: &SimState, order: &OrderRequest) -> Result<(), ExchangeError> {
+        // what is rejected before the order reaches the book
+        let rules = match self.rules.get(&order.symbol) {
+            Some(rules) => rules,
+            None => return Err(rejected(-1121, "Invalid symbol.")),
+        };
+        let (best_bid, best_ask) = match state.books.get(&order.symbol) {
+            Some(book) => (book.best_bid(), book.best_ask()),
+            None => return Err(ExchangeError::Other("no book yet".to_string())),
+        };
+        if order.qty <= 0.0 {
+            return Err(rejected(-4003, "Quantity less than or equal to zero."));
+        }
+        let is_buy = order.side == Side::Buy;
+        let position = state.positions.get(&order.symbol).copied().unwrap_or(0.0);
+        let reduce_only = order.reduce_only.unwrap_or(false);
+        if reduce_only && (position == 0.0 || (position > 0.0) == is_buy) {
+            return Err(rejected(-2022, "ReduceOnly Order is rejected."));
+        }
+        let px = order.price.unwrap_or(match is_buy {
+            true => best_ask,
+            false => best_bid,
+        });
+        if !reduce_only && rules.notional(px, order.qty) < rules.min_notional {
+            return Err(rejected(
+                -4164,
+                &format!(
+                    "Order's notional must be no smaller than {} (unless you choose reduce only).",
+                    rules.min_notional
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Exchange for SimExchange {
+    fn place_order(
+        &self,
+        order: &OrderRequest,
+        client_order_id: &str,
+    ) -> Result<OrderAck, ExchangeError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.failures.is_empty() {
+            return Err(state.failures.remove(0));
+        }
+        self.check_order(&state, order)?;
+        let reduce_only = order.reduce_only.unwrap_or(false);
+
+        state.next_order_id += 1;
+        let ack = OrderAck {
+            order_id: state.next_order_id,
+            client_order_id: client_order_id.to_string(),
+            orig_qty: order.qty,
+            executed_qty: 0.0,
+            price: order.price.unwrap_or(0.0),
+            side: order.side.as_str().to_string(),
+            order_type: match order.order_type {
+                OrderType::Limit => "LIMIT".to_string(),
+                OrderType::Market => "MARKET".to_string(),
+            },
+            reduce_only: reduce_only,
+            position_side: order.position_side.clone().unwrap_or("BOTH".to_string()),
+            time_in_force: match order.time_in_force {
+                Some(TimeInForce::PostOnly) => "GTX".to_string(),
+                Some(TimeInForce::Ioc) => "IOC".to_string(),
+                None => "GTC".to_string(),
+            },
+            status: "NEW".to_string(),
+            update_time: state.now,
+        };
+        state.orders.insert(
+            client_order_id.to_string(),
+            SimOrder {
+                ack: ack.clone(),
+                request: order.clone(),
+                active: false,
+                queue_ahead: 0.0,
+                cumulative_quote: 0.0,
+            },
+        );
+        match self.fill_model.latency_ms {
+            0 => {
+                if let Err(e) = state.activate(client_order_id, self.fill_model.queue_fraction) {
+                    state.orders.remove(client_order_id);
+                    return Err(e);
+                }
+            }
+            _ => self.send(&mut state, Pending::Activate(client_order_id.to_string())),
+        }
+        Ok(ack)
+    }
+
+    fn test_order(
+        &self,
+        order: &OrderRequest,
+        _client_order_id: &str,
+    ) -> Result<(), ExchangeError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.failures.is_empty() {
+            return Err(state.failures.remove(0));
+        }
+        self.check_order(&state, order)
+    }
+
+    fn modify_order(
+        &self,
+        symbol: &str,
+        client_order_id: &str,
+        _side: Side,
+        qty: f64,
+        px: f64,
+    ) -> Result<OrderAck, ExchangeError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.failures.is_empty() {
+            return Err(state.failures.remove(0));
+        }
+        let mut ack = match state.orders.get(client_order_id) {
+            Some(order) if order.request.symbol == symbol && order.is_open() => order.ack.clone(),
+            _ => return Err(rejected(-2013, "Order does not exist.")),
+        };
+        match self.fill_model.latency_ms {
+            0 => state.modify(client_order_id, qty, px, self.fill_model.queue_fraction),
+            _ => {
+                self.send(
+                    &mut state,
+                    Pending::Modify(client_order_id.to_string(), qty, px),
+                );
+                ack.orig_qty = qty;
+                ack.price = px;
+                Ok(ack)
+            }
+        }
+    }
+
+    fn cancel_order(&self, symbol: &str, client_order_id: &str) -> Result<(), ExchangeError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.failures.is_empty() {
+            return Err(state.failures.remove(0));
+        }
+        match state.orders.get(client_order_id) {
+            Some(order) if order.request.symbol == symbol && order.is_open() => {}
+            _ => return Err(rejected(-2011, "Unknown order sent.")),
+        }
+        match self.fill_model.latency_ms {
+            0 => state.cancel(client_order_id),
+            _ => {
+                self.send(&mut state, Pending::Cancel(client_order_id.to_string()));
+                Ok(())
+            }
+        }
+    }
+
+    fn query_order(&self, symbol: &str, client_order_id: &str) -> Result<OrderAck, ExchangeError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.failures.is_empty() {
+            return Err(state.failures.remove(0));
+        }
+        match state.orders.get(client_order_id) {
+            Some(order) if order.request.symbol == symbol => Ok(order.ack.clone()),
+            _ => Err(rejected(-2013, "Order does not exist.")),
+        }
+    }
+
+    fn open_orders(&self, symbol: &str) -> Result<Vec<OrderAck>, ExchangeError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .open_orders(symbol)
+            .iter()
+            .map(|client_order_id| state.orders[client_order_id].ack.clone())
+            .collect())
+    }
+
+    fn position_risk(&self, symbol: &str) -> Result<Vec<PositionRisk>, ExchangeError> {
+        let state = self.state.lock().unwrap();
+        let mark_price = match state.books.get(symbol) {
+            Some(book) => (book.best_bid() + book.best_ask()) / 2.0,
+            None => 0.0,
+        };
+        Ok(vec![PositionRisk {
+            symbol: symbol.to_string(),
+            position_amt: state.positions.get(symbol).copied().unwrap_or(0.0),
+            mark_price: mark_price,
+            position_side: "BOTH".to_string(),
+        }])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SimEvent {
+    Book(Book),
+    Trade(MarketTrade),
+}
+
+impl SimEvent {
+    pub fn symbol(&self) -> &str {
+        match self {
+            SimEvent::Book(book) => &book.symbol,
+            SimEvent::Trade(trade) => &trade.symbol,
+        }
+    }
+}
+
+pub fn deliver_events(
+    exchange: &SimExchange,
+    traders: &HashMap<String, Arc<Mutex<SymbolTrader>>>,
+    recorder: Option<&Recorder>,
+) {
+    for event in exchange.take_events() {
+        if let Some(recorder) = recorder {
+            recorder.order(&event);
+        }
+        traders[&event.symbol]
+            .lock()
+            .unwrap()
+            .on_trade_update(event);
+    }
+}
+
+pub fn on_market_event(
+    exchange: &SimExchange,
+    traders: &HashMap<String, Arc<Mutex<SymbolTrader>>>,
+    event: SimEvent,
+    recorder: Option<&Recorder>,
+) {
+    // the exchange matches first, our fills reach the trader before it sees the new book
+    let trader = &traders[event.symbol()];
+    match &event {
+        SimEvent::Book(book) => exchange.on_book(book),
+        SimEvent::Trade(trade) => exchange.on_trade(trade),
+    }
+    deliver_events(exchange, traders, recorder);
+    match event {
+        SimEvent::Book(book) => trader.lock().unwrap().handle_price_event(book),
+        SimEvent::Trade(trade) => trader.lock().unwrap().handle_market_trade(trade),
+    }
+    deliver_events(exchange, traders, recorder);
+}
+
+pub fn run_sim(
+    positions: Vec<Position>,
+    exchange: Arc<SimExchange>,
+    script: Vec<SimEvent>,
+    leftovers: &str,
+) -> Result<Vec<TraderSummary>, String> {
+    // run_positions against the simulated exchange, one-way mode and no batching. the script stands
+    // in for the streams, the run ends once everything is filled or the script runs out
+    let keep_running = Arc::new(AtomicBool::new(true));
+    let run_id = new_run_id(0);
+    let traders: Vec<Arc<Mutex<SymbolTrader>>> = positions
+        .iter()
+        .enumerate()
+        .map(|(idx, x)| {
+            Arc::new(Mutex::new(SymbolTrader::new(
+                x.clone(),
+                Arc::clone(&keep_running),
+                exchange.rules(&x.symbol),
+                Arc::clone(&exchange) as Arc<dyn Exchange>,
+                None,
+                ClientOrderIds::new(&run_id, idx),
+            )))
+        })
+        .collect();
+    let by_symbol: HashMap<String, Arc<Mutex<SymbolTrader>>> = traders
+        .iter()
+        .map(|trader| {
+            let key = trader.lock().unwrap().position.symbol.to_string();
+            (key, Arc::clone(trader))
+        })
+        .collect();
+
+    // the account's position is marked at the first book, as it would be before connecting
+    for event in script.iter() {
+        if let SimEvent::Book(book) = event {
+            if !exchange.has_book(&book.symbol) {
+                exchange.on_book(book);
+            }
+        }
+    }
+    let leftover_policy = LeftoverAction::from(leftovers);
+    for trader in traders.iter() {
+        let mut trader = trader.lock().unwrap();
+        trader.set_position_mode(false)?;
+        trader.resolve_position()?;
+        // everything already in the simulated book predates the run
+        trader.reconcile(leftover_policy, u64::MAX);
+    }
+    deliver_events(&exchange, &by_symbol, None);
+
+    for event in script {
+        if traders.iter().all(|x| x.lock().unwrap().filled) {
+            break;
+        }
+        if by_symbol.contains_key(event.symbol()) {
+            on_market_event(&exchange, &by_symbol, event, None);
+        }
+    }
+
+    // like an interrupt, whatever is still resting is pulled
+    for trader in traders.iter() {
+        trader.lock().unwrap().shutdown();
+    }
+    exchange.flush();
+    deliver_events(&exchange, &by_symbol, None);
+    keep_running.swap(false, std::sync::atomic::Ordering::Release);
+
+    Ok(traders
+        .iter()
+        .map(|x| TraderSummary::from(&*x.lock().unwrap()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::Level;
+
+    fn book(event_time: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Book {
+        let levels = |levels: &[(f64, f64)]| -> Vec<Level> {
+            levels
+                .iter()
+                .map(|(px, qty)| Level { px: *px, qty: *qty })
+                .collect()
+        };
+        Book {
+            symbol: "BTCUSDT".to_string(),
+            event_time: event_time,
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    fn trade(trade_time: u64, px: f64, qty: f64) -> MarketTrade {
+        MarketTrade {
+            symbol: "BTCUSDT".to_string(),
+            trade_time: trade_time,
+            px: px,
+            qty: qty,
+        }
+    }
+
+    fn rules() -> SymbolRules {
+        SymbolRules {
+            tick_size: 0.1,
+            min_price: 1.0,
+            max_price: 1000000.0,
+            step_size: 0.001,
+            min_qty: 0.001,
+            max_qty: 1000.0,
+            min_notional: 5.0,
+            contract_size: None,
+        }
+    }
+
+    fn position(qty: f64) -> Position {
+        Position {
+            symbol: "BTCUSDT".to_string(),
+            notional: 0.0,
+            reduce_only: false,
+            sz_type: "q".to_string(),
+            qty: qty,
+            ..Default::default()
+        }
+    }
+
+    fn exchange() -> Arc<SimExchange> {
+        Arc::new(SimExchange::new(
+            HashMap::from([("BTCUSDT".to_string(), rules())]),
+            FillModel::default(),
+        ))
+    }
+
+    fn post_only(side: Side, qty: f64, px: f64) -> OrderRequest {
+        OrderRequest {
+            symbol: "BTCUSDT".to_string(),
+            side: side,
+            order_type: OrderType::Limit,
+            time_in_force: Some(TimeInForce::PostOnly),
+            qty: qty,
+            price: Some(px),
+            reduce_only: Some(false),
+            position_side: None,
+        }
+    }
+
+    fn code(response: Result<OrderAck, ExchangeError>) -> i16 {
+        match response {
+            Err(ExchangeError::Rejected { code, .. }) => code,
+            _ => panic!("should be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_order_acceptance() {
+        let exchange = exchange();
+        exchange.on_book(&book(0, &[(100.0, 2.0)], &[(100.1, 1.0)]));
+        assert_eq!(
+            code(exchange.place_order(&post_only(Side::Buy, 1.0, 100.1), "a")),
+            -5022
+        );
+        assert_eq!(
+            code(exchange.place_order(&post_only(Side::Buy, 0.01, 100.0), "b")),
+            -4164
+        );
+        let mut reduce = post_only(Side::Sell, 1.0, 100.1);
+        reduce.reduce_only = Some(true);
+        assert_eq!(code(exchange.place_order(&reduce, "c")), -2022);
+
+        exchange.fail_next(ExchangeError::UnknownStatus);
+        assert_eq!(
+            exchange
+                .place_order(&post_only(Side::Buy, 1.0, 100.0), "d")
+                .err(),
+            Some(ExchangeError::UnknownStatus)
+        );
+        assert_eq!(code(exchange.query_order("BTCUSDT", "d")), -2013);
+        let ack = exchange
+            .place_order(&post_only(Side::Buy, 1.0, 100.0), "d")
+            .unwrap();
+        assert_eq!(ack.status, "NEW");
+        assert_eq!(exchange.open_orders("BTCUSDT").unwrap().len(), 1);
+        assert_eq!(exchange.cancel_order("BTCUSDT", "d"), Ok(()));
+        assert_eq!(
+            exchange.cancel_order("BTCUSDT", "d"),
+            Err(rejected(-2011, "Unknown order sent."))
+        );
+        let statuses: Vec<String> = exchange
+            .take_events()
+            .into_iter()
+            .map(|event| event.order_status)
+            .collect();
+        assert_eq!(statuses, vec!["NEW", "CANCELED"]);
+    }
+
+    #[test]
+    fn test_queue_fills() {
+        let exchange = exchange();
+        exchange.on_book(&book(0, &[(100.0, 2.0)], &[(100.1, 1.0)]));
+        exchange
+            .place_order(&post_only(Side::Buy, 1.0, 100.0), "a")
+            .unwrap();
+        // 2.0 ahead in the queue
+        exchange.on_trade(&trade(1, 100.0, 1.5));
+        assert_eq!(exchange.position("BTCUSDT"), 0.0);
+        exchange.on_trade(&trade(2, 100.0, 1.0));
+        assert_eq!(exchange.position("BTCUSDT"), 0.5);
+        exchange.on_trade(&trade(3, 99.9, 0.1));
+        assert_eq!(exchange.position("BTCUSDT"), 1.0);
+        let fills: Vec<(f64, String)> = exchange
+            .take_events()
+            .into_iter()
+            .filter(|event| event.execution_type == "TRADE")
+            .map(|event| (event.last_filled_qty, event.order_status))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (0.5, "PARTIALLY_FILLED".to_string()),
+                (0.5, "FILLED".to_string())
+            ]
+        );
+
+        // a market order walks the book
+        let mut sell = post_only(Side::Sell, 2.5, 0.0);
+        sell.order_type = OrderType::Market;
+        sell.time_in_force = None;
+        sell.price = None;
+        exchange.on_book(&book(4, &[(100.0, 2.0), (99.9, 1.0)], &[(100.1, 1.0)]));
+        exchange.place_order(&sell, "b").unwrap();
+        assert_eq!(exchange.position("BTCUSDT"), -1.5);
+        assert_eq!(
+            exchange.query_order("BTCUSDT", "b").unwrap().executed_qty,
+            2.5
+        );
+    }
+
+    #[test]
+    fn test_run_sim() {
+        let exchange = exchange();
+        let position = Position {
+            deadline_secs: Some(1.0),
+            ..position(2.0)
+        };
+        let script = vec![
+            SimEvent::Book(book(0, &[(100.0, 1.0)], &[(100.1, 5.0)])),
+            // 1.0 ahead, then half the order
+            SimEvent::Trade(trade(100, 100.0, 2.0)),
+            SimEvent::Book(book(200, &[(100.0, 1.0)], &[(100.1, 5.0)])),
+            // the deadline pulls the rest and sweeps it
+            SimEvent::Book(book(1000, &[(100.0, 1.0)], &[(100.1, 5.0)])),
+            SimEvent::Book(book(1100, &[(100.0, 1.0)], &[(100.1, 5.0)])),
+            SimEvent::Book(book(1200, &[(100.0, 1.0)], &[(100.1, 5.0)])),
+        ];
+        let summary = run_sim(vec![position], Arc::clone(&exchange), script, "cancel").unwrap();
+        assert_eq!(summary[0].status, "filled");
+        assert_eq!(summary[0].matched_qty, Some(2.0));
+        assert!((summary[0].avg_entry.unwrap() - 100.05).abs() < 1e-9);
+        assert_eq!(summary[0].cancels, 1);
+        assert_eq!(exchange.position("BTCUSDT"), 2.0);
+    }
+
+    #[test]
+    fn test_run_sim_flip() {
+        let exchange = exchange();
+        exchange.set_position("BTCUSDT", -1.0);
+        let position = Position {
+            target: true,
+            ..position(1.0)
+        };
+        let script = vec![
+            SimEvent::Book(book(0, &[(100.0, 1.0)], &[(100.1, 5.0)])),
+            // the ask comes down through the reduce-only close leg
+            SimEvent::Book(book(100, &[(99.9, 1.0)], &[(100.0, 5.0)])),
+            SimEvent::Book(book(200, &[(99.9, 1.0)], &[(100.0, 5.0)])),
+            SimEvent::Trade(trade(300, 99.8, 1.0)),
+            SimEvent::Book(book(400, &[(99.9, 1.0)], &[(100.0, 5.0)])),
+        ];
+        let summary = run_sim(vec![position], Arc::clone(&exchange), script, "cancel").unwrap();
+        assert_eq!(summary[0].start_qty, Some(-1.0));
+        assert_eq!(summary[0].legs.len(), 2);
+        assert_eq!(summary[0].status, "filled");
+        assert_eq!(exchange.position("BTCUSDT"), 1.0);
+    }
+
+    #[test]
+    fn test_run_sim_delta_flip() {
+        // a plain delta through zero is split like a target
+        let exchange = exchange();
+        exchange.set_position("BTCUSDT", -1.0);
+        let script = vec![
+            SimEvent::Book(book(0, &[(100.0, 1.0)], &[(100.1, 5.0)])),
+            SimEvent::Book(book(100, &[(99.9, 1.0)], &[(100.0, 5.0)])),
+            SimEvent::Book(book(200, &[(99.9, 1.0)], &[(100.0, 5.0)])),
+            SimEvent::Trade(trade(300, 99.8, 1.0)),
+            SimEvent::Book(book(400, &[(99.9, 1.0)], &[(100.0, 5.0)])),
+        ];
+        let summary =
+            run_sim(vec![position(2.0)], Arc::clone(&exchange), script, "cancel").unwrap();
+        assert_eq!(summary[0].start_qty, Some(-1.0));
+        assert_eq!(
+            summary[0]
+                .legs
+                .iter()
+                .map(|(qty, reduce_only, _)| (*qty, *reduce_only))
+                .collect::<Vec<(f64, bool)>>(),
+            vec![(1.0, true), (1.0, false)]
+        );
+        assert_eq!(summary[0].status, "filled");
+        assert_eq!(exchange.position("BTCUSDT"), 1.0);
+    }
+}
@@ -3,16 +3,35 @@ use binance::{
     config::Config,
     errors::Error,
     futures::{userstream::FuturesUserStream, websockets::*},
+    model::{AggrTradesEvent, DepthOrderBookEvent},
+    userstream::UserStream,
 };
 use std::{
     collections::HashMap,
     env,
     fmt::Debug,
+    io::ErrorKind,
+    net::TcpStream,
     sync::{atomic::AtomicBool, Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tungstenite::connect;
+use tungstenite::{connect, stream::MaybeTlsStream, Message, WebSocket};
 
-use crate::trader::SymbolTrader;
+use crate::{
+    events::SpotExecutionReport,
+    exchange::{Book, Level, MarketTrade, OrderEvent},
+    recorder::Recorder,
+    rest::Rest,
+    sim::SimEvent,
+    trader::SymbolTrader,
+    venue::Venue,
+};
+
+// spot listen keys expire after 60 minutes without a keepalive
+const SPOT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const SPOT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+const SPOT_RECONNECT_WAIT: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone)]
 pub struct Keys {
@@ -29,13 +48,8 @@ impl Keys {
     }
 }
 
-pub fn get_config() -> Config {
-    // -----
-    // only use combined streams
-    // the single is untested and probably is broken
-    // -----
-
-    let debug = match env::var("DEBUG")
+fn is_debug() -> bool {
+    match env::var("DEBUG")
         .expect("DEBUG doesnt exist")
         .parse::<i32>()
         .unwrap()
@@ -45,8 +59,16 @@ pub fn get_config() -> Config {
         _ => {
             panic!("wrong debug val in .env")
         }
-    };
-    if debug {
+    }
+}
+
+pub fn get_config() -> Config {
+    // -----
+    // only use combined streams
+    // the single is untested and probably is broken
+    // -----
+
+    if is_debug() {
         let testnet_config = Config::testnet();
         let testnet_config =
             testnet_config.set_futures_ws_endpoint("wss://stream.binancefuture.com");
@@ -101,10 +123,23 @@ impl<'a> ExtendedFuturesWebSocketsTrait<'a> for FuturesWebSockets<'a> {
     }
 }
 
+fn start_user_stream(rest: &Rest) -> String {
+    match rest.venue {
+        // binance-rs' FuturesUserStream is usd-m only
+        Venue::CoinM => rest
+            .start_user_stream()
+            .expect("couldnt start the coin-m user stream"),
+        _ => keep_user_stream_alive(),
+    }
+}
+
 pub fn init_stream<'a>(
     traders: HashMap<String, Arc<Mutex<SymbolTrader>>>,
     keep_running: Arc<AtomicBool>,
+    rest: Rest,
+    recorder: Option<Arc<Recorder>>,
 ) {
+    // usd-m or coin-m, the events are the same on both
     let mut streams: Vec<String> = traders
         .clone()
         .iter()
@@ -120,12 +155,29 @@ pub fn init_stream<'a>(
             )
         })
         .collect();
+    // the trades drive pov, a recording always has them for the backtest's queue fills
+    let market_trade_streams: Vec<String> = traders
+        .iter()
+        .map(|f| f.1.lock().unwrap())
+        .filter(|trader| trader.participation.is_some() || recorder.is_some())
+        .map(|trader| format!("{}@aggTrade", trader.position.symbol.to_lowercase()))
+        .collect();
+    streams.extend(market_trade_streams);
     let callback_fn = {
         let mut traders = traders;
+        let rest = rest.clone();
 
         move |event: FuturesWebsocketEvent| {
             match event {
                 FuturesWebsocketEvent::DepthOrderBook(event) => {
+                    if let Some(recorder) = &recorder {
+                        let stream = format!("{}@depth5@100ms", event.symbol.to_lowercase());
+                        recorder.parsed(&stream, &event);
+                    }
+                    let event = Book::from(event);
+                    if let Some(recorder) = &recorder {
+                        recorder.book(&event);
+                    }
                     traders
                         .get_mut(&event.symbol)
                         .unwrap()
@@ -133,6 +185,22 @@ pub fn init_stream<'a>(
                         .unwrap()
                         .handle_price_event(event);
                 }
+                FuturesWebsocketEvent::AggrTrades(event) => {
+                    if let Some(recorder) = &recorder {
+                        let stream = format!("{}@aggTrade", event.symbol.to_lowercase());
+                        recorder.parsed(&stream, &event);
+                    }
+                    let event = MarketTrade::from(event);
+                    if let Some(recorder) = &recorder {
+                        recorder.trade(&event);
+                    }
+                    traders
+                        .get_mut(&event.symbol)
+                        .unwrap()
+                        .lock()
+                        .unwrap()
+                        .handle_market_trade(event);
+                }
                 FuturesWebsocketEvent::UserDataStreamExpiredEvent(event) => {
                     match event.event_type.to_string().as_str() {
                         "TRADE_LITE" => {
@@ -141,13 +209,20 @@ pub fn init_stream<'a>(
                         _ => {
                             dbg!(event);
                             println!("keep_user_stream_alive");
-                            keep_user_stream_alive();
+                            start_user_stream(&rest);
                         }
                     }
                 }
                 FuturesWebsocketEvent::OrderTrade(event) => {
+                    if let Some(recorder) = &recorder {
+                        recorder.parsed("user", &event);
+                    }
+                    let event = OrderEvent::from(event);
+                    if let Some(recorder) = &recorder {
+                        recorder.order(&event);
+                    }
                     traders
-                        .get_mut(&event.order.symbol)
+                        .get_mut(&event.symbol)
                         .unwrap()
                         .lock()
                         .unwrap()
@@ -165,12 +240,19 @@ pub fn init_stream<'a>(
 
     let mut web_socket = FuturesWebSockets::new(callback_fn);
 
-    let listen_key = keep_user_stream_alive();
+    let listen_key = start_user_stream(&rest);
     dbg!(&listen_key);
     streams.push(listen_key);
 
     println!("listening to streams {:#?}", &streams);
     let config = get_config();
+    let config = match rest.venue {
+        Venue::CoinM => {
+            let endpoint = rest.venue.ws_endpoint(&config);
+            config.set_futures_ws_endpoint(endpoint)
+        }
+        _ => config,
+    };
     web_socket
         .connect_multiple_streams_with_config(&streams, config)
         .expect("bug");
@@ -185,3 +267,234 @@ pub fn init_stream<'a>(
         },
     }
 }
+
+fn parse_level(level: &serde_json::Value) -> (f64, f64) {
+    let parse = |value: &serde_json::Value| {
+        value
+            .as_str()
+            .and_then(|value| value.parse::<f64>().ok())
+            .expect("parsable string. wont fail unless binance breaks it")
+    };
+    (parse(&level[0]), parse(&level[1]))
+}
+
+pub fn partial_book(symbol: &str, data: &serde_json::Value) -> Book {
+    // the spot partial book stream and the depth endpoints, no symbol and no event time on spot.
+    // it is stamped on arrival
+    let event_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let levels = |side: &serde_json::Value| -> Vec<Level> {
+        side.as_array()
+            .unwrap()
+            .iter()
+            .map(parse_level)
+            .map(|(px, qty)| Level { px: px, qty: qty })
+            .collect()
+    };
+    Book {
+        symbol: symbol.to_uppercase(),
+        event_time: event_time,
+        bids: levels(&data["bids"]),
+        asks: levels(&data["asks"]),
+    }
+}
+
+pub fn market_data_url(venue: Venue, streams: &[String]) -> String {
+    // combined public streams, no listen key
+    let endpoint = venue.ws_endpoint(&get_config());
+    format!("{}/stream?streams={}", endpoint, &streams.join("/"))
+}
+
+pub fn parse_market_data(venue: Venue, stream: &str, data: &serde_json::Value) -> SimEvent {
+    // a message of the streams at market_data_url
+    let (symbol, channel) = stream.split_once('@').expect("combined stream name");
+    match (channel, venue) {
+        ("depth5@100ms", Venue::Spot) => SimEvent::Book(partial_book(symbol, data)),
+        ("depth5@100ms", _) => SimEvent::Book(Book::from(
+            serde_json::from_value::<DepthOrderBookEvent>(data.clone()).unwrap(),
+        )),
+        ("aggTrade", _) => SimEvent::Trade(MarketTrade::from(
+            serde_json::from_value::<AggrTradesEvent>(data.clone()).unwrap(),
+        )),
+        _ => {
+            dbg!(stream);
+            panic!("unexpected market data stream");
+        }
+    }
+}
+
+fn on_spot_message(
+    traders: &HashMap<String, Arc<Mutex<SymbolTrader>>>,
+    listen_key: &str,
+    recorder: Option<&Recorder>,
+    message: serde_json::Value,
+) -> bool {
+    // false once the listen key expired, the stream is reconnected with a new one
+    let stream = message["stream"].as_str().unwrap_or_default();
+    let data = &message["data"];
+    if let Some(recorder) = recorder {
+        // the listen key is no use once the run is over
+        match stream == listen_key {
+            true => recorder.raw("user", data),
+            false => recorder.raw(stream, data),
+        }
+    }
+    if stream == listen_key {
+        match data["e"].as_str() {
+            Some("executionReport") => {
+                let report: SpotExecutionReport = serde_json::from_value(data.clone()).unwrap();
+                let event = OrderEvent::from(report);
+                if let Some(recorder) = recorder {
+                    recorder.order(&event);
+                }
+                // the account may trade other symbols at the same time
+                if let Some(trader) = traders.get(&event.symbol) {
+                    trader.lock().unwrap().on_trade_update(event);
+                }
+            }
+            Some("listenKeyExpired") => {
+                println!("spot listen key expired, reconnecting");
+                return false;
+            }
+            // outboundAccountPosition, balanceUpdate
+            _ => {}
+        }
+        return true;
+    }
+    let (symbol, channel) = stream.split_once('@').expect("combined stream name");
+    let trader = traders.get(&symbol.to_uppercase()).unwrap();
+    match channel {
+        "depth5@100ms" => {
+            let event = partial_book(symbol, data);
+            if let Some(recorder) = recorder {
+                recorder.book(&event);
+            }
+            trader.lock().unwrap().handle_price_event(event)
+        }
+        "aggTrade" => {
+            let event =
+                MarketTrade::from(serde_json::from_value::<AggrTradesEvent>(data.clone()).unwrap());
+            if let Some(recorder) = recorder {
+                recorder.trade(&event);
+            }
+            trader.lock().unwrap().handle_market_trade(event)
+        }
+        _ => {
+            dbg!(stream);
+            panic!("unexpected spot stream");
+        }
+    }
+    true
+}
+
+pub fn set_read_timeout(socket: &WebSocket<MaybeTlsStream<TcpStream>>, timeout: Duration) {
+    let stream = match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream,
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref(),
+        _ => unreachable!("only native-tls is enabled"),
+    };
+    stream
+        .set_read_timeout(Some(timeout))
+        .expect("couldnt set the read timeout");
+}
+
+pub fn init_spot_stream(
+    traders: HashMap<String, Arc<Mutex<SymbolTrader>>>,
+    keep_running: Arc<AtomicBool>,
+    recorder: Option<Arc<Recorder>>,
+) {
+    // book, trades and user data on one combined stream like init_stream. the messages are parsed
+    // here, spot events are turned into the types the trader already handles
+    let keys = Keys::new();
+    let config = get_config();
+    let user_stream: UserStream =
+        Binance::new_with_config(Some(keys.api_key), Some(keys.secret_key), &config);
+
+    let market_streams: Vec<String> = traders
+        .values()
+        .map(|trader| trader.lock().unwrap())
+        .flat_map(|trader| {
+            let symbol = trader.position.symbol.to_lowercase();
+            match trader.participation.is_some() || recorder.is_some() {
+                true => vec![
+                    format!("{}@depth5@100ms", symbol),
+                    format!("{}@aggTrade", symbol),
+                ],
+                false => vec![format!("{}@depth5@100ms", symbol)],
+            }
+        })
+        .collect();
+    println!("listening to spot streams {:#?}", &market_streams);
+
+    // a dropped connection or an expired listen key reconnects with a new key
+    let mut reconnecting = false;
+    while keep_running.load(std::sync::atomic::Ordering::Acquire) {
+        if reconnecting {
+            thread::sleep(SPOT_RECONNECT_WAIT);
+        }
+        let listen_key = match user_stream.start() {
+            Ok(answer) => answer.listen_key,
+            Err(e) => {
+                println!("couldnt start the spot user stream, {}", e);
+                reconnecting = true;
+                continue;
+            }
+        };
+        let mut streams = market_streams.clone();
+        streams.push(listen_key.to_string());
+        let url = format!(
+            "{}/stream?streams={}",
+            config.ws_endpoint,
+            &streams.join("/")
+        );
+        let mut socket = match connect(url) {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                println!("couldnt connect to the spot streams, {}", e);
+                reconnecting = true;
+                continue;
+            }
+        };
+        // the keepalive and keep_running are checked between reads, also on a quiet stream
+        set_read_timeout(&socket, SPOT_READ_TIMEOUT);
+        if reconnecting {
+            for trader in traders.values() {
+                trader.lock().unwrap().resync_order();
+            }
+        }
+        reconnecting = true;
+
+        let mut kept_alive_at = Instant::now();
+        while keep_running.load(std::sync::atomic::Ordering::Acquire) {
+            if kept_alive_at.elapsed() >= SPOT_KEEP_ALIVE_INTERVAL {
+                match user_stream.keep_alive(&listen_key) {
+                    Ok(_) => kept_alive_at = Instant::now(),
+                    // retried after the next read
+                    Err(e) => println!("couldnt keep the spot listen key alive, {}", e),
+                }
+            }
+            match socket.read() {
+                Ok(Message::Text(text)) => {
+                    let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if !on_spot_message(&traders, &listen_key, recorder.as_deref(), message) {
+                        break;
+                    }
+                }
+                Ok(Message::Close(frame)) => {
+                    println!("spot stream closed with {:?}, reconnecting", frame);
+                    break;
+                }
+                // pings are answered by tungstenite
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => {
+                    println!("spot stream broke with {}, reconnecting", e);
+                    break;
+                }
+            }
+        }
+    }
+}
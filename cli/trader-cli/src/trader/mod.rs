@@ -1,27 +1,40 @@
-use crate::utils::Round;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::ToPyObject;
 use std::{
     collections::HashMap,
     fmt::{self, Debug},
     sync::{atomic::AtomicBool, Arc, Mutex},
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use std::{thread, vec};
 
-use binance::futures::model::OrderTradeEvent;
-use binance::{
-    account::OrderSide,
-    errors::{BinanceContentError, Error, ErrorKind},
-    futures::{
-        account::{CustomOrderRequest, FuturesAccount, OrderType},
-        model::{Symbol, Transaction},
+use crate::{
+    batch::{start_flusher, BatchCoordinator},
+    exchange::{
+        Book, Exchange, ExchangeError, MarketTrade, OrderAck, OrderEvent, OrderRequest, OrderType,
+        Side, TimeInForce,
     },
-    model::DepthOrderBookEvent,
+    guard::{GuardAction, PriceGuard},
+    ids::{new_run_id, ClientOrderIds},
+    paper::init_paper_stream,
+    participation::ParticipationTracker,
+    position::{Position, SizeType},
+    position_target::{self, Leg},
+    pricing::{self, ImproveTicks, PricingStrategy},
+    ratelimit::RateLimiter,
+    requote::RequotePolicy,
+    reconcile::{self, LeftoverAction},
+    recorder::Recorder,
+    rest::Rest,
+    rules::{RuleViolation, SymbolRules},
+    schedule::{TwapSchedule, Urgency, UrgencySchedule},
+    sim::{FillModel, SimExchange},
+    subscriber::{init_spot_stream, init_stream},
+    utils::get_futures_account,
+    venue::Venue,
 };
 
-use crate::{position::Position, subscriber::init_stream, utils::get_futures_account};
-
 #[derive(PartialEq)]
 enum OrderStatus {
     New,
@@ -29,6 +42,8 @@ enum OrderStatus {
     Filled,
     PartiallyFilled,
     Trade,
+    Expired,
+    Amendment,
 }
 
 impl OrderStatus {
@@ -39,6 +54,8 @@ impl OrderStatus {
             "FILLED" => OrderStatus::Filled,
             "PARTIALLY_FILLED" => OrderStatus::PartiallyFilled,
             "TRADE" => OrderStatus::Trade,
+            "EXPIRED" => OrderStatus::Expired,
+            "AMENDMENT" => OrderStatus::Amendment,
             _ => {
                 dbg!(s);
                 panic!("Invalid order status")
@@ -47,6 +64,17 @@ impl OrderStatus {
     }
 }
 
+// the outcome of a validation run for one position
+#[derive(Debug, Clone, PartialEq)]
+pub enum Validation {
+    // every order went through the test endpoint, or there was nothing to send
+    Accepted,
+    // the first error as the venue sent it
+    Rejected(String),
+    // coin-m has no test endpoint
+    Unsupported,
+}
+
 #[derive(Debug, Clone)]
 struct PriceInfo {
     best_bid: f64,
@@ -57,15 +85,19 @@ struct PriceInfo {
 
 #[derive(Debug, Clone)]
 pub struct Order {
-    pub transaction: Transaction,
+    pub ack: OrderAck,
     pub px: f64,
+    pub urgency: Urgency,
+    pub placed_at: u64,
 }
 
 impl Order {
-    pub fn new(transaction: Transaction, px: f64) -> Self {
+    pub fn new(ack: OrderAck, px: f64, urgency: Urgency, placed_at: u64) -> Self {
         Order {
-            transaction: transaction,
+            ack: ack,
             px: px,
+            urgency: urgency,
+            placed_at: placed_at,
         }
     }
 }
@@ -73,20 +105,66 @@ impl Order {
 #[derive(Clone)]
 pub struct SymbolTrader {
     pub position: Position,
-    pub latest_orderbook_event: Option<DepthOrderBookEvent>,
-    pub trade_events: Vec<OrderTradeEvent>,
+    pub latest_orderbook_event: Option<Book>,
+    pub trade_events: Vec<OrderEvent>,
     pub order: Option<Order>,
     pub keep_running: Arc<AtomicBool>, //external
     pub inflight: Arc<AtomicBool>,
-    pub account: FuturesAccount,
-    pub info: Symbol,
+    // a placement or cancel is queued with the batch flusher, cleared by its response
+    pub pending: bool,
+    pub exchange: Arc<dyn Exchange>,
+    pub venue: Venue,
+    pub rules: SymbolRules,
     pub filled: bool,
     pub avg_entry: Option<f64>,
     pub price_at_start: Option<f64>,
     pub matched_qty: Option<f64>,
     pub mids: Vec<Mid>,
+    pub pricing: Arc<dyn PricingStrategy>,
+    pub schedule: Option<UrgencySchedule>,
+    pub twap: Option<TwapSchedule>,
+    pub participation: Option<ParticipationTracker>,
+    pub urgency: Urgency,
+    pub urgency_changes: Vec<(String, u64)>,
+    pub guard: Option<PriceGuard>,
+    pub paused: bool,
+    pub guard_events: Vec<(String, u64)>,
+    pub requote_policy: RequotePolicy,
+    pub stale_since: Option<u64>,
+    pub requoting: bool,
+    pub cancels: u32,
+    pub replaces: u32,
+    pub amends: u32,
+    pub batch: Option<Arc<BatchCoordinator>>,
+    pub client_ids: ClientOrderIds,
+    // (client order id, px, urgency) of a placement whose outcome is unknown
+    pub unconfirmed: Option<(String, f64, Urgency)>,
+    // -2013 answers to the lookup so far and when to look again, see confirm_order
+    pub confirm_attempts: u32,
+    pub confirm_after: u64,
+    // (client order id, action) for every order found in the book at start
+    pub leftovers: Vec<(String, String)>,
+    // set on interrupt, nothing new is placed after this
+    pub stopped: bool,
+    // the account's position when the run started, only read when resolve_position needs it
+    pub start_qty: Option<f64>,
+    pub legs: Vec<Leg>,
+    pub leg: usize,
+    pub hedge_mode: bool,
+    // the test endpoint's answer in a validation run
+    pub validation: Option<Validation>,
+    // the answer that stopped the trader, see stop_on_error
+    pub error: Option<String>,
 }
 
+// quoted while escalating, before the deadline sweep
+const IMPROVE_PRICING: ImproveTicks = ImproveTicks { ticks: 1 };
+
+// a timed out order can show up a while after the timeout, it is only given up on after this many
+// "Order does not exist." answers, the wait doubling from CONFIRM_BACKOFF_MS
+const CONFIRM_ATTEMPTS: u32 = 3;
+const CONFIRM_BACKOFF_MS: u64 = 500;
+
 impl fmt::Debug for SymbolTrader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SymbolTrader")
@@ -95,6 +173,8 @@ impl fmt::Debug for SymbolTrader {
             .field("trade_events", &self.trade_events)
             .field("order", &self.order)
             .field("keep_running", &self.keep_running)
+            .field("pricing", &self.pricing)
+            .field("urgency", &self.urgency)
             .finish()
     }
 }
@@ -103,8 +183,10 @@ pub trait Trader {
     fn new(
         position: Position,
         keep_running: Arc<AtomicBool>,
-        info: Symbol,
-        account: FuturesAccount,
+        rules: SymbolRules,
+        exchange: Arc<dyn Exchange>,
+        batch: Option<Arc<BatchCoordinator>>,
+        client_ids: ClientOrderIds,
     ) -> Self;
 }
 
@@ -129,9 +211,19 @@ impl Trader for SymbolTrader {
     fn new(
         position: Position,
         keep_running: Arc<AtomicBool>,
-        info: Symbol,
-        account: FuturesAccount,
+        rules: SymbolRules,
+        exchange: Arc<dyn Exchange>,
+        batch: Option<Arc<BatchCoordinator>>,
+        client_ids: ClientOrderIds,
     ) -> SymbolTrader {
+        let pricing = pricing::from_spec(&position.pricing).expect("invalid pricing strategy");
+        let participation = position.pov_rate.map(ParticipationTracker::new);
+        let requote_policy = RequotePolicy {
+            min_ticks: position.requote_ticks,
+            delay_ms: position.requote_delay_ms,
+            min_rest_ms: position.min_rest_ms,
+        };
+        let venue = position.venue();
         let symbol_trader = SymbolTrader {
             position: position,
             order: None,
@@ -139,37 +231,315 @@ impl Trader for SymbolTrader {
             trade_events: vec![],
             keep_running: keep_running,
             inflight: Arc::new(AtomicBool::new(false)),
-            account: account,
-            info: info,
+            pending: false,
+            exchange: exchange,
+            venue: venue,
+            rules: rules,
             filled: false,
             avg_entry: None,
             price_at_start: None,
             matched_qty: None,
             mids: vec![],
+            pricing: pricing,
+            schedule: None,
+            twap: None,
+            participation: participation,
+            urgency: Urgency::Passive,
+            urgency_changes: vec![],
+            guard: None,
+            paused: false,
+            guard_events: vec![],
+            requote_policy: requote_policy,
+            stale_since: None,
+            requoting: false,
+            cancels: 0,
+            replaces: 0,
+            amends: 0,
+            batch: batch,
+            client_ids: client_ids,
+            unconfirmed: None,
+            confirm_attempts: 0,
+            confirm_after: 0,
+            leftovers: vec![],
+            stopped: false,
+            start_qty: None,
+            legs: vec![],
+            leg: 0,
+            hedge_mode: false,
+            validation: None,
+            error: None,
         };
         symbol_trader
     }
 }
 
 impl SymbolTrader {
-    pub fn handle_price_event(&mut self, event: DepthOrderBookEvent) {
+    pub fn set_position_mode(&mut self, hedge_mode: bool) -> Result<(), String> {
+        // a misconfiguration comes back as an error, run_binance raises it as ValueError
+        if !self.venue.has_positions() {
+            // rejected by Position::validate already, a spot sell is bounded by the free balance
+            return match self.position.reduce_only
+                || self.position.target
+                || self.position.position_side.is_some()
+            {
+                true => Err(format!(
+                    "{} reduce_only, target and position_side need a futures venue",
+                    self.position.symbol
+                )),
+                false => Ok(()),
+            };
+        }
+        // in hedge mode every order names the LONG or SHORT position it opens or closes
+        self.hedge_mode = hedge_mode;
+        match (hedge_mode, &self.position.position_side) {
+            (true, Some(_)) => {}
+            // picked with the account's position, see resolve_position
+            (true, None) if self.position.target => {}
+            (true, None) => {
+                self.position.position_side = Some(
+                    position_target::infer_position_side(self.is_long(), self.position.reduce_only)
+                        .to_string(),
+                );
+            }
+            (false, Some(_)) => {
+                return Err(format!(
+                    "{} has a position_side but the account is in one-way mode",
+                    self.position.symbol
+                ))
+            }
+            (false, None) => {}
+        }
+        Ok(())
+    }
+
+    pub fn resolve_position(&mut self) -> Result<(), String> {
+        // reads the account's position before anything looks at the side. in target mode the
+        // target becomes the qty delta to trade, a delta that flips the position is split in legs
+        if !self.venue.has_positions() {
+            return Ok(());
+        }
+        // a plain delta needs it to split a flip in one-way mode or to cap a close in hedge mode,
+        // hedge mode opens and reduce-only closes in one-way mode can't go through zero
+        let needs_position = match (self.position.target, self.hedge_mode) {
+            (true, _) => true,
+            (false, true) => self.position.reduce_only,
+            (false, false) => !self.position.reduce_only,
+        };
+        if !needs_position {
+            return Ok(());
+        }
+        let risks = self
+            .exchange
+            .position_risk(&self.position.symbol)
+            .expect("couldnt fetch position risk");
+        let side_qty = |position_side: &str| -> f64 {
+            risks
+                .iter()
+                .filter(|risk| risk.position_side == position_side)
+                .map(|risk| risk.position_amt)
+                .sum()
+        };
+        let mark_price = risks.first().expect("no position risk").mark_price;
+        // the target in target mode, the delta otherwise
+        let sized_qty = match self.position.size_type() {
+            SizeType::Notional => self
+                .rules
+                .qty_for_notional(self.position.notional, mark_price),
+            SizeType::Qty => self.position.qty,
+        };
+        if self.position.target && self.hedge_mode && self.position.position_side.is_none() {
+            let position_side = position_target::target_position_side(
+                sized_qty,
+                side_qty("LONG"),
+                side_qty("SHORT"),
+            )
+            .map_err(|e| format!("{} {}", self.position.symbol, e))?;
+            self.position.position_side = Some(position_side.to_string());
+        }
+        let position_side = self
+            .position
+            .position_side
+            .clone()
+            .unwrap_or("BOTH".to_string());
+        let current_qty = side_qty(&position_side);
+        self.start_qty = Some(current_qty);
+        if self.position.target {
+            let target_qty = sized_qty;
+            if self.hedge_mode
+                && !((position_side == "LONG" && target_qty >= 0.0)
+                    || (position_side == "SHORT" && target_qty <= 0.0))
+            {
+                return Err(format!(
+                    "{} target {} is on the wrong side of the {} position",
+                    self.position.symbol, target_qty, position_side
+                ));
+            }
+            let (delta, reduce_only) = position_target::resolve(current_qty, target_qty);
+            println!(
+                "{} at {}, target {}, trading {}",
+                self.position.symbol, current_qty, target_qty, delta
+            );
+            self.position.sz_type = "q".to_string();
+            self.position.qty = delta;
+            self.position.reduce_only = reduce_only;
+            if self.rules.round_qty(delta) == 0.0 {
+                println!("{} already at target", self.position.symbol);
+                self.set_filled();
+                return Ok(());
+            }
+        }
+        let delta = match self.position.size_type() {
+            SizeType::Notional => sized_qty,
+            SizeType::Qty => self.position.qty,
+        };
+        if self.hedge_mode {
+            // reduceOnly can't be sent in hedge mode, a close is capped to the side's size instead
+            if self.position.reduce_only && delta.abs() > current_qty.abs() {
+                println!(
+                    "{} capping the close of {} at {}",
+                    self.position.symbol, position_side, current_qty
+                );
+                self.position.sz_type = "q".to_string();
+                self.position.qty = -current_qty;
+            }
+            return Ok(());
+        }
+        if self.position.reduce_only {
+            return Ok(());
+        }
+        if let Some(legs) = position_target::split_flip(current_qty, delta) {
+            println!(
+                "{} flips from {}, closing then opening {:?}",
+                self.position.symbol, current_qty, legs
+            );
+            // legs are in qty, the size is tracked exactly from here
+            self.position.sz_type = "q".to_string();
+            self.position.qty = delta;
+            self.position.reduce_only = legs[0].reduce_only;
+            self.legs = legs;
+        }
+        Ok(())
+    }
+
+    fn get_leg_remaining(&self) -> Option<f64> {
+        // signed qty left in the current leg
+        if self.legs.is_empty() {
+            return None;
+        }
+        let legs_qty: f64 = self.legs[..=self.leg].iter().map(|leg| leg.qty).sum();
+        Some(legs_qty - self.get_matched_qty().unwrap_or(0.0))
+    }
+
+    fn update_leg(&mut self, timestamp: u64) {
+        // the close leg is done once the existing size is matched
+        if self.leg + 1 >= self.legs.len() {
+            return;
+        }
+        let remaining = self.get_leg_remaining().unwrap();
+        if self.rules.round_qty(remaining) == 0.0 {
+            self.next_leg(timestamp);
+        }
+    }
+
+    fn next_leg(&mut self, timestamp: u64) -> bool {
+        if self.leg + 1 >= self.legs.len() {
+            return false;
+        }
+        self.legs[self.leg].completed_at = Some(timestamp);
+        self.leg += 1;
+        self.position.reduce_only = self.legs[self.leg].reduce_only;
+        println!(
+            "{} closed, opening {}",
+            self.position.symbol, self.legs[self.leg].qty
+        );
+        true
+    }
+
+    pub fn reconcile(&mut self, policy: LeftoverAction, started_at: u64) {
+        // runs before the streams start, a crashed run may have left orders resting. started_at is
+        // in ms since the epoch, see reconcile::is_leftover
+        let open_orders = self
+            .exchange
+            .open_orders(&self.position.symbol)
+            .expect("couldnt fetch open orders");
+        for open_order in open_orders {
+            if !reconcile::is_leftover(&open_order, self.client_ids.run_id(), started_at) {
+                let owner = match reconcile::is_ours(&open_order) {
+                    true => "a run still going",
+                    false => "another tool",
+                };
+                println!(
+                    "{} has an open order {} from {}, leaving it",
+                    self.position.symbol, open_order.client_order_id, owner
+                );
+                continue;
+            }
+            // nothing is filled yet, a notional is converted at the leftover's price
+            let remaining = self.get_target() - self.get_filled();
+            let remaining_qty = match self.position.size_type() {
+                SizeType::Notional => self.rules.qty_for_notional(remaining, open_order.price),
+                SizeType::Qty => remaining,
+            };
+            let remaining_qty = match self.get_leg_remaining() {
+                Some(leg_remaining) => remaining_qty.abs().min(leg_remaining.abs()),
+                None => remaining_qty.abs(),
+            };
+            let action = reconcile::decide(
+                policy,
+                &open_order,
+                self.is_long(),
+                self.position.reduce_only,
+                self.position.position_side.as_deref().unwrap_or("BOTH"),
+                remaining_qty,
+                self.order.is_some(),
+            );
+            let outcome = match action {
+                LeftoverAction::Ignore => "ignored",
+                LeftoverAction::Adopt => {
+                    let px = open_order.price;
+                    let placed_at = open_order.update_time;
+                    self.order = Some(Order::new(
+                        open_order.clone(),
+                        px,
+                        Urgency::Passive,
+                        placed_at,
+                    ));
+                    "adopted"
+                }
+                LeftoverAction::Cancel => match self
+                    .exchange
+                    .cancel_order(&self.position.symbol, &open_order.client_order_id)
+                {
+                    Ok(_) => "cancelled",
+                    Err(e) => match &e {
+                        ExchangeError::Rejected { code: -2011, .. } => "gone",
+                        _ => {
+                            dbg!(e);
+                            panic!("couldnt cancel leftover order");
+                        }
+                    },
+                },
+            };
+            println!(
+                "{} leftover order {} {}",
+                self.position.symbol, open_order.client_order_id, outcome
+            );
+            self.leftovers
+                .push((open_order.client_order_id, outcome.to_string()));
+        }
+    }
+
+    pub fn handle_price_event(&mut self, event: Book) {
         self.latest_orderbook_event = Some(event);
         self.on_orderbook_update();
     }
 
-    fn get_min_notional(&self) -> f64 {
-        self.info
-            .filters
-            .iter()
-            .find_map(|filter| match filter {
-                binance::model::Filters::MinNotional { notional, .. } => {
-                    Some(notional.as_ref().unwrap())
-                }
-                _ => None,
-            })
-            .unwrap()
-            .parse::<f64>()
-            .unwrap()
+    pub fn handle_market_trade(&mut self, event: MarketTrade) {
+        let (px, qty) = (event.px, event.qty);
+        let notional = self.rules.notional(px, qty);
+        if let Some(participation) = self.participation.as_mut() {
+            participation.on_market_trade(event.trade_time, qty, notional);
+        }
     }
 
     fn get_last_ts(&self) -> Option<u64> {
@@ -186,13 +556,7 @@ impl SymbolTrader {
                 let matched_qty: f64 = self
                     .trade_events
                     .iter()
-                    .map(|trade_event| {
-                        trade_event
-                            .order
-                            .qty_last_filled_trade
-                            .parse::<f64>()
-                            .unwrap()
-                    })
+                    .map(|trade_event| trade_event.last_filled_qty)
                     .sum();
 
                 Some(match self.is_long() {
@@ -208,20 +572,26 @@ impl SymbolTrader {
         let avg_entry = match self.trade_events.len() {
             0 => None,
             _ => {
-                let total_traded_val: f64 = self
-                    .trade_events
-                    .iter()
-                    .map(|trade_event| {
-                        trade_event.order.qty.parse::<f64>().unwrap()
-                            * trade_event.order.price.parse::<f64>().unwrap()
-                    })
-                    .sum();
-                let total_sz: f64 = self
-                    .trade_events
-                    .iter()
-                    .map(|trade_event| trade_event.order.qty.parse::<f64>().unwrap())
-                    .sum();
-                Some(total_traded_val / total_sz)
+                // market orders have no order price, so weight the actual fills
+                let trades = self.get_trades();
+                let total_sz: f64 = trades.iter().map(|trade| trade.qty).sum();
+                let avg_entry = match self.rules.contract_size {
+                    // a coin-m contract is a fixed usd amount, the entry is the harmonic mean
+                    Some(_) => {
+                        let total_coins: f64 =
+                            trades.iter().map(|trade| trade.qty / trade.px).sum();
+                        total_sz / total_coins
+                    }
+                    None => {
+                        let total_traded_val: f64 =
+                            trades.iter().map(|trade| trade.qty * trade.px).sum();
+                        total_traded_val / total_sz
+                    }
+                };
+                match total_sz > 0.0 {
+                    true => Some(avg_entry),
+                    false => None,
+                }
             }
         };
 
@@ -236,21 +606,7 @@ impl SymbolTrader {
         let total_fills = self
             .trade_events
             .iter()
-            .map(|x| {
-                let qty_last_filled_trade = x
-                    .order
-                    .qty_last_filled_trade
-                    .parse::<f64>()
-                    .expect("parsable string. wont fail unless binance breaks it");
-
-                let average_price = x
-                    .order
-                    .average_price
-                    .parse::<f64>()
-                    .expect("parsable string. wont fail unless binance breaks it");
-
-                average_price * qty_last_filled_trade
-            })
+            .map(|x| self.rules.notional(x.average_px, x.last_filled_qty))
             .reduce(|a, b| a + b)
             .unwrap();
 
@@ -264,36 +620,144 @@ impl SymbolTrader {
         self.trade_events
             .iter()
             .map(|x| Trade {
-                px: x
-                    .order
-                    .price_last_filled_trade
-                    .parse::<f64>()
-                    .expect("parsable string. wont fail unless binance breaks it"),
-                qty: x
-                    .order
-                    .qty_last_filled_trade
-                    .parse::<f64>()
-                    .expect("parsable string. wont fail unless binance breaks it"),
+                px: x.last_filled_px,
+                qty: x.last_filled_qty,
                 timestamp: x.transaction_time,
             })
             .collect()
     }
 
     fn is_long(&self) -> bool {
-        self.position.notional > 0.0
+        self.position.is_long()
+    }
+
+    fn get_pricing(&self) -> &dyn PricingStrategy {
+        match self.urgency {
+            Urgency::Passive => self.pricing.as_ref(),
+            Urgency::Improve | Urgency::Sweep => &IMPROVE_PRICING,
+        }
     }
 
     fn is_stale(&mut self) -> bool {
-        let price_info = self.get_price_info();
-        match self.is_long() {
-            true => self.order.as_ref().expect("will always exist").px < price_info.best_bid,
-            false => self.order.as_ref().expect("will always exist").px > price_info.best_ask,
+        let order = self.order.as_ref().expect("will always exist");
+        self.get_pricing().is_stale(
+            order.px,
+            self.latest_orderbook_event.as_ref().unwrap(),
+            self.is_long(),
+            self.rules.tick_size,
+        )
+    }
+
+    fn should_requote(&mut self, timestamp: u64) -> bool {
+        let order = self.order.clone().expect("will always exist");
+        match (order.urgency, self.urgency) {
+            // the sweep is IOC/market, wait for it to fill or expire
+            (Urgency::Sweep, _) => return false,
+            // escalations skip the hysteresis
+            (order_urgency, urgency) if order_urgency != urgency => return true,
+            _ => {}
+        }
+        let rested_for_ms = timestamp.saturating_sub(order.placed_at);
+        if self.is_undersized() {
+            return rested_for_ms >= self.requote_policy.min_rest_ms;
+        }
+        if !self.is_stale() {
+            self.stale_since = None;
+            return false;
+        }
+        let stale_since = *self.stale_since.get_or_insert(timestamp);
+        let drift_ticks = (self.get_px() - order.px).abs() / self.rules.tick_size;
+        self.requote_policy.should_requote(
+            drift_ticks,
+            timestamp.saturating_sub(stale_since),
+            rested_for_ms,
+        )
+    }
+
+    fn is_undersized(&mut self) -> bool {
+        // a new twap slice was released or the pov allowance grew while the order was resting
+        if self.twap.is_none() && self.participation.is_none() {
+            return false;
+        }
+        let order_id = self.order.as_ref().unwrap().ack.order_id;
+        let order_filled: f64 = self
+            .get_fills_for_order(order_id)
+            .iter()
+            .map(|trade| trade.qty)
+            .sum();
+        let order_open_qty = self.order.as_ref().unwrap().ack.orig_qty - order_filled;
+        let working_qty = self.get_working_qty();
+        let working_qty = self.rules.round_qty(working_qty).abs();
+        match self.participation.is_some() {
+            // requoting on every print would churn, wait until the order could at least double
+            true => working_qty >= 2.0 * order_open_qty,
+            false => working_qty >= order_open_qty + self.rules.step_size,
         }
     }
 
-    fn calc_is_filled(&self) -> bool {
-        let min_notional_filter = self.get_min_notional();
-        self.get_remaining_notional().abs() < min_notional_filter
+    fn get_fills_for_order(&self, order_id: u64) -> Vec<Trade> {
+        self.trade_events
+            .iter()
+            .zip(self.get_trades())
+            .filter(|(trade_event, _)| trade_event.order_id == order_id)
+            .map(|(_, trade)| trade)
+            .collect()
+    }
+
+    fn start(&mut self, price_info: &PriceInfo) {
+        // on the first tick, everything time or arrival price based is anchored here
+        let timestamp = price_info.timestamp;
+        self.schedule = Some(UrgencySchedule::new(
+            timestamp,
+            self.position.escalate_after_secs,
+            self.position.deadline_secs,
+        ));
+        if let Some(twap_secs) = self.position.twap_secs {
+            self.twap = Some(TwapSchedule::new(
+                timestamp,
+                twap_secs,
+                self.position.twap_slices,
+                self.position.twap_jitter,
+            ));
+        }
+        if let Some(participation) = self.participation.as_mut() {
+            participation.start(timestamp);
+        }
+        self.guard = PriceGuard::new(
+            self.is_long(),
+            self.position.limit_px,
+            self.position.max_slippage_bps,
+            self.price_at_start.unwrap(),
+            GuardAction::from(&self.position.on_limit),
+        );
+    }
+
+    fn update_urgency(&mut self, timestamp: u64) {
+        let urgency = self.schedule.as_ref().unwrap().urgency_at(timestamp);
+        if urgency != self.urgency {
+            println!(
+                "{} switching from {} to {}",
+                self.position.symbol,
+                self.urgency.as_str(),
+                urgency.as_str()
+            );
+            self.urgency = urgency;
+            self.urgency_changes
+                .push((urgency.as_str().to_string(), timestamp));
+        }
+    }
+
+    fn calc_is_filled(&mut self) -> bool {
+        match (self.position.size_type(), self.rules.contract_size) {
+            (SizeType::Notional, None) => {
+                self.get_remaining_notional().abs() < self.rules.min_notional
+            }
+            // coin-m has no min notional, done once less than a contract is left
+            (SizeType::Notional, Some(_)) | (SizeType::Qty, _) => {
+                let remaining_qty = self.get_remaining_qty();
+                self.rules.round_qty(remaining_qty) == 0.0
+            }
+        }
     }
 
     fn on_orderbook_update(&mut self) {
@@ -302,10 +766,37 @@ impl SymbolTrader {
             mid: price_info.mid,
             timestamp: price_info.timestamp,
         });
+        if self.stopped {
+            return;
+        }
+        if self.schedule.is_none() {
+            self.start(&price_info);
+        }
+        self.update_urgency(price_info.timestamp);
+        self.update_leg(price_info.timestamp);
+        if self.is_guard_breached(&price_info) {
+            return;
+        }
+        if self.unconfirmed.is_some() {
+            if price_info.timestamp >= self.confirm_after {
+                self.confirm_order();
+            }
+            return;
+        }
         match &self.order {
             Some(_) => {
-                match self.is_stale() {
-                    true => self.cancel_order(),
+                match self.should_requote(price_info.timestamp) {
+                    // the sweep changes the order type, that can't be amended. spot has no modify
+                    true => match self.position.amend
+                        && self.venue.has_positions()
+                        && self.urgency != Urgency::Sweep
+                    {
+                        true => self.amend_order(),
+                        false => {
+                            self.requoting = true;
+                            self.cancel_order();
+                        }
+                    },
                     false => (),
                 };
             }
@@ -315,32 +806,169 @@ impl SymbolTrader {
         }
     }
 
+    fn is_guard_breached(&mut self, price_info: &PriceInfo) -> bool {
+        // pulls the resting order and stops quoting while the touch is beyond the limit
+        let guard = match &self.guard {
+            Some(guard) => guard.clone(),
+            None => return false,
+        };
+        let touch = match self.is_long() {
+            true => price_info.best_bid,
+            false => price_info.best_ask,
+        };
+        match guard.is_breached(touch) {
+            true => {
+                if !self.paused {
+                    let reason = format!("touch {} beyond limit {}", touch, guard.limit_px);
+                    println!("{} pausing, {}", self.position.symbol, reason);
+                    self.guard_events.push((reason, price_info.timestamp));
+                    self.paused = true;
+                }
+                match self.order.is_some() {
+                    true => {
+                        self.requoting = false;
+                        self.cancel_order();
+                    }
+                    false => {
+                        if guard.action == GuardAction::Abort && !self.filled {
+                            println!("{} aborting on limit", self.position.symbol);
+                            self.guard_events
+                                .push(("aborted".to_string(), price_info.timestamp));
+                            self.filled = true;
+                        }
+                    }
+                }
+                true
+            }
+            false => {
+                if self.paused {
+                    println!("{} resuming", self.position.symbol);
+                    self.guard_events
+                        .push(("resumed".to_string(), price_info.timestamp));
+                    self.paused = false;
+                }
+                false
+            }
+        }
+    }
+
     fn get_remaining_notional(&self) -> f64 {
         let total_fills_notional = self.get_sum_fills();
         self.position.notional - total_fills_notional
     }
 
+    fn get_target(&self) -> f64 {
+        //in the position's sizing unit
+        match self.position.size_type() {
+            SizeType::Notional => self.position.notional,
+            SizeType::Qty => self.position.qty,
+        }
+    }
+
+    fn get_filled(&self) -> f64 {
+        match self.position.size_type() {
+            SizeType::Notional => self.get_sum_fills(),
+            SizeType::Qty => self.get_matched_qty().unwrap_or(0.0),
+        }
+    }
+
+    fn to_qty(&mut self, sz: f64) -> f64 {
+        // qty sized positions are tracked exactly, notional ones are converted at the current mid
+        match self.position.size_type() {
+            SizeType::Notional => {
+                let mid = self.get_price_info().mid;
+                self.rules.qty_for_notional(sz, mid)
+            }
+            SizeType::Qty => sz,
+        }
+    }
+
+    fn get_remaining_qty(&mut self) -> f64 {
+        let remaining = self.get_target() - self.get_filled();
+        self.to_qty(remaining)
+    }
+
+    fn get_working_qty(&mut self) -> f64 {
+        // the part of the remaining qty that can be worked right now, after twap slicing and child caps
+        let target = self.get_target();
+        let timestamp = self.get_price_info().timestamp;
+        let released = match (&self.twap, self.urgency) {
+            (Some(twap), Urgency::Passive | Urgency::Improve) => {
+                target * twap.released_fraction(timestamp)
+            }
+            _ => target,
+        };
+        let released = match (&self.participation, self.urgency) {
+            (Some(participation), Urgency::Passive | Urgency::Improve) => {
+                let allowed = match self.position.size_type() {
+                    SizeType::Notional => participation.allowed_notional(),
+                    SizeType::Qty => participation.allowed_qty(),
+                };
+                released.signum() * released.abs().min(allowed)
+            }
+            _ => released,
+        };
+        let working = released - self.get_filled();
+        // ahead of the schedule
+        if working * target <= 0.0 {
+            return 0.0;
+        }
+        let working_qty = self.to_qty(working);
+        let working_qty = match self.position.max_child_notional {
+            Some(max_child_notional) => {
+                let mid = self.get_price_info().mid;
+                let max_child_qty = self.rules.qty_for_notional(max_child_notional, mid);
+                working_qty.signum() * working_qty.abs().min(max_child_qty)
+            }
+            None => working_qty,
+        };
+        // the reduce-only close leg never goes past flat
+        match self.get_leg_remaining() {
+            Some(leg_remaining) => {
+                working_qty.signum() * working_qty.abs().min(leg_remaining.abs())
+            }
+            None => working_qty,
+        }
+    }
+
     fn get_sz_px(&mut self) -> (f64, f64) {
-        let price_info = self.get_price_info();
-        let remaining_notional = self.get_remaining_notional();
+        let working_qty = self.get_working_qty();
 
         println!(
-            "symbol: {} self.position.notional: {}, remaining notinal:{}",
-            self.position.symbol, self.position.notional, &remaining_notional
+            "symbol: {} self.position.notional: {}, self.position.qty: {}, working qty:{}",
+            self.position.symbol, self.position.notional, self.position.qty, &working_qty
         );
-        let sz =
-            (remaining_notional / price_info.mid).round_to_n(self.info.quantity_precision as i32);
-        let px = match self.is_long() {
-            true => price_info.best_bid,
-            false => price_info.best_ask,
+        let sz = self.rules.round_qty(working_qty);
+        (sz, self.get_px())
+    }
+
+    fn get_px(&mut self) -> f64 {
+        let px = match self.urgency {
+            // the sweep crosses the spread, px is only used for the filters and reporting
+            Urgency::Sweep => {
+                let price_info = self.get_price_info();
+                match self.is_long() {
+                    true => price_info.best_ask,
+                    false => price_info.best_bid,
+                }
+            }
+            _ => self.get_pricing().quote(
+                self.latest_orderbook_event.as_ref().unwrap(),
+                self.is_long(),
+                self.rules.tick_size,
+            ),
+        };
+        let px = match &self.guard {
+            Some(guard) => guard.clamp(px),
+            None => px,
         };
-        (sz, px)
+        self.rules.round_price(px, self.is_long())
     }
 
     fn get_price_info(&mut self) -> PriceInfo {
         let latest_orderbook_event = self.latest_orderbook_event.as_ref().unwrap();
-        let best_bid = latest_orderbook_event.bids.get(0).unwrap().price;
-        let best_ask = latest_orderbook_event.asks.get(0).unwrap().price;
+        let best_bid = latest_orderbook_event.best_bid();
+        let best_ask = latest_orderbook_event.best_ask();
 
         let mid = (best_bid + best_ask) / 2.0;
 
@@ -357,38 +985,142 @@ impl SymbolTrader {
     }
 
     fn cancel_order(&mut self) {
-        if self.inflight.load(std::sync::atomic::Ordering::Acquire) {
+        if self.inflight.load(std::sync::atomic::Ordering::Acquire) || self.pending {
             println!("cancelledorder inflight");
             return ();
         }
         self.inflight
             .swap(true, std::sync::atomic::Ordering::Release);
-        let cancel_order = self.account.cancel_order(
-            &self.position.symbol,
-            self.order.clone().unwrap().transaction.order_id,
-        );
-        let success: Result<(), Error> = match cancel_order {
+        let client_order_id = self.order.clone().unwrap().ack.client_order_id;
+        match self.batch.clone() {
+            // the flusher hands the response back through on_cancel_response
+            Some(batch) => {
+                batch.queue_cancel(&self.position.symbol, &client_order_id);
+                self.pending = true;
+                self.inflight
+                    .swap(false, std::sync::atomic::Ordering::Release);
+            }
+            None => {
+                let response = self
+                    .exchange
+                    .cancel_order(&self.position.symbol, &client_order_id);
+                self.on_cancel_response(response);
+            }
+        }
+    }
+
+    pub fn on_cancel_response(&mut self, response: Result<(), ExchangeError>) {
+        self.pending = false;
+        let success: Result<(), ExchangeError> = match response {
             Ok(_) => {
                 self.order = None;
+                self.stale_since = None;
+                self.cancels += 1;
+                Ok(())
+            }
+            // the order stays live, the cancel is retried on the next tick
+            Err(ExchangeError::RateLimited) => Ok(()),
+            Err(ExchangeError::UnknownStatus) => {
+                self.lookup_order();
                 Ok(())
             }
-            Err(e) => match &e.0 {
-                ErrorKind::BinanceError(BinanceContentError { code, msg }) => match code {
+            Err(e) => match &e {
+                ExchangeError::Rejected { code, .. } => match code {
                     -2011 => {
                         //println!("the order either have been matched or cancelled");
                         self.order = None;
                         Ok(())
                     }
                     _ => {
-                        dbg!(code);
-                        dbg!(msg);
-                        todo!("error at cancel");
+                        self.stop_on_error(&e);
+                        Err(e)
                     }
                 },
+                // an answer that couldnt be read, the cancel may have gone through
                 _ => {
-                    dbg!("unhandled error");
-                    dbg!(e);
-                    panic!("handle");
+                    println!("{} cancel failed, {}", self.position.symbol, e);
+                    self.lookup_order();
+                    Ok(())
+                }
+            },
+        };
+
+        self.inflight
+            .swap(success.is_err(), std::sync::atomic::Ordering::Release);
+    }
+
+    fn amend_order(&mut self) {
+        if self.inflight.load(std::sync::atomic::Ordering::Acquire) || self.pending {
+            return ();
+        }
+        self.inflight
+            .swap(true, std::sync::atomic::Ordering::Release);
+        let (sz, px) = self.get_sz_px();
+        let order = self.order.clone().unwrap();
+        let order_filled: f64 = self
+            .get_fills_for_order(order.ack.order_id)
+            .iter()
+            .map(|trade| trade.qty)
+            .sum();
+        if self.rules.validate(px, sz, self.position.reduce_only).is_err() {
+            // let the cancel/replace path deal with the violation
+            self.inflight
+                .swap(false, std::sync::atomic::Ordering::Release);
+            self.requoting = true;
+            self.cancel_order();
+            return ();
+        }
+        let side_enum = match self.is_long() {
+            true => Side::Buy,
+            false => Side::Sell,
+        };
+        // the modified quantity is the new total, including what already filled
+        let qty = self.rules.round_qty(order_filled + sz.abs());
+        let modify_order = self.exchange.modify_order(
+            &self.position.symbol,
+            &order.ack.client_order_id,
+            side_enum,
+            qty,
+            px,
+        );
+        let success: Result<(), ExchangeError> = match modify_order {
+            Ok(ack) => {
+                let placed_at = self.get_price_info().timestamp;
+                self.order = Some(Order::new(ack, px, self.urgency, placed_at));
+                self.stale_since = None;
+                self.amends += 1;
+                Ok(())
+            }
+            Err(ExchangeError::RateLimited) => Ok(()),
+            Err(ExchangeError::UnknownStatus) => {
+                // the modify may have gone through, the next tick requotes from what is resting
+                self.lookup_order();
+                Ok(())
+            }
+            Err(e) => match &e {
+                ExchangeError::Rejected { code, msg } => {
+                    match code {
+                        // "No need to modify the order."
+                        -5027 => {
+                            self.stale_since = None;
+                            Ok(())
+                        }
+                        _ => {
+                            // e.g. the order filled in the meantime, the cancel sorts out what is left
+                            println!("modify rejected with {} {}, cancelling instead", code, msg);
+                            self.inflight
+                                .swap(false, std::sync::atomic::Ordering::Release);
+                            self.requoting = true;
+                            self.cancel_order();
+                            return ();
+                        }
+                    }
+                }
+                // an answer that couldnt be read, the modify may have gone through
+                _ => {
+                    println!("{} modify failed, {}", self.position.symbol, e);
+                    self.lookup_order();
+                    Ok(())
                 }
             },
         };
@@ -398,41 +1130,146 @@ impl SymbolTrader {
     }
 
     fn place_marketable_order(&mut self) {
-        if self.inflight.load(std::sync::atomic::Ordering::Acquire) {
+        if self.inflight.load(std::sync::atomic::Ordering::Acquire) || self.pending {
             return ();
         }
         self.inflight
             .swap(true, std::sync::atomic::Ordering::Release);
+        let (order, px) = match self.marketable_order() {
+            Ok(order) => order,
+            Err(violation) => {
+                let success = self.on_rule_violation(violation);
+                self.inflight
+                    .swap(success.is_err(), std::sync::atomic::Ordering::Release);
+                return ();
+            }
+        };
+        let urgency = self.urgency;
+        let client_order_id = self.client_ids.next();
+        match self.batch.clone() {
+            Some(batch) => {
+                batch.queue_order(order, &client_order_id, px, urgency);
+                self.pending = true;
+                self.inflight
+                    .swap(false, std::sync::atomic::Ordering::Release);
+            }
+            None => {
+                let response = self.exchange.place_order(&order, &client_order_id);
+                self.on_order_response(response, &client_order_id, px, urgency);
+            }
+        }
+    }
+
+    fn marketable_order(&mut self) -> Result<(OrderRequest, f64), RuleViolation> {
+        // the order to send now and its px, checked against the symbol's filters
         let (sz, px) = self.get_sz_px();
         println!("{} @ {}", &sz, &px);
-        let side_enum = match self.position.notional < 0.0 {
-            true => OrderSide::Sell,
-            false => OrderSide::Buy,
-        };
-        let order = CustomOrderRequest {
-            activation_price: None,
-            callback_rate: None,
-            close_position: Some(false),
-            order_type: OrderType::Limit,
-            time_in_force: Some(binance::futures::account::TimeInForce::GTX),
-            position_side: None,
-            price_protect: None,
-            price: Some(px),
-            qty: Some(sz.abs()),
-            reduce_only: Some(self.position.reduce_only),
-            side: side_enum,
-            stop_price: None,
-            symbol: self.position.symbol.to_string(),
-            working_type: None,
+        self.rules.validate(px, sz, self.position.reduce_only)?;
+        Ok((self.order_request(sz, px), px))
+    }
+
+    fn order_request(&self, sz: f64, px: f64) -> OrderRequest {
+        let side_enum = match self.is_long() {
+            true => Side::Buy,
+            false => Side::Sell,
+        };
+        let (order_type, time_in_force, price) = match (self.urgency, &self.guard) {
+            // with a limit in place the sweep is an IOC at the clamped price instead of a market order
+            (Urgency::Sweep, Some(_)) => (OrderType::Limit, Some(TimeInForce::Ioc), Some(px)),
+            (Urgency::Sweep, None) => (OrderType::Market, None, None),
+            _ => (OrderType::Limit, Some(TimeInForce::PostOnly), Some(px)),
         };
+        OrderRequest {
+            symbol: self.position.symbol.to_string(),
+            side: side_enum,
+            order_type: order_type,
+            time_in_force: time_in_force,
+            qty: sz.abs(),
+            price: price,
+            // rejected in hedge mode, the position side says whether it opens or closes
+            reduce_only: match self.hedge_mode || !self.venue.has_positions() {
+                true => None,
+                false => Some(self.position.reduce_only),
+            },
+            // futures only, never set for spot
+            position_side: self.position.position_side.clone(),
+        }
+    }
 
-        let success = match self.account.custom_order(order) {
-            Ok(transaction) => {
-                self.order = Some(Order::new(transaction, px.clone()));
+    pub fn validate_order(&mut self, book: Book) {
+        // the first order of each leg as the first book would place it, sent to the test endpoint
+        // instead. the symbol's filters are left to the venue so the error is the one it would send
+        if self.venue.order_test_path().is_none() {
+            self.validation = Some(Validation::Unsupported);
+            return;
+        }
+        if self.filled {
+            // already at target, nothing to send
+            self.validation = Some(Validation::Accepted);
+            return;
+        }
+        self.latest_orderbook_event = Some(book);
+        let price_info = self.get_price_info();
+        self.start(&price_info);
+        self.update_urgency(price_info.timestamp);
+        loop {
+            let (sz, px) = self.get_sz_px();
+            // the opening leg of a flip as if the close had filled
+            let sz = match self.leg {
+                0 => sz,
+                _ => {
+                    let leg_qty = self.rules.round_qty(self.legs[self.leg].qty);
+                    sz.signum() * sz.abs().min(leg_qty.abs())
+                }
+            };
+            let order = self.order_request(sz, px);
+            let client_order_id = self.client_ids.next();
+            if let Err(e) = self.exchange.test_order(&order, &client_order_id) {
+                self.validation = Some(Validation::Rejected(e.to_string()));
+                return;
+            }
+            if !self.next_leg(price_info.timestamp) {
+                break;
+            }
+        }
+        self.validation = Some(Validation::Accepted);
+    }
+
+    pub fn on_order_response(
+        &mut self,
+        response: Result<OrderAck, ExchangeError>,
+        client_order_id: &str,
+        px: f64,
+        urgency: Urgency,
+    ) {
+        self.pending = false;
+        let success = match response {
+            Ok(ack) => {
+                let placed_at = self.get_price_info().timestamp;
+                self.order = Some(Order::new(ack, px.clone(), urgency, placed_at));
+                if self.requoting {
+                    self.replaces += 1;
+                    self.requoting = false;
+                }
+                // a batched placement can land after the shutdown
+                if self.stopped {
+                    self.cancel_on_shutdown();
+                }
                 Ok(())
             }
-            Err(e) => match &e.0 {
-                ErrorKind::BinanceError(BinanceContentError { code, msg }) => {
+            Err(ExchangeError::RateLimited) => Ok(()),
+            Err(ExchangeError::UnknownStatus) => {
+                // timed out, the order may be live. never place again before looking it up
+                self.unconfirmed = Some((client_order_id.to_string(), px, urgency));
+                self.confirm_attempts = 0;
+                self.confirm_after = 0;
+                self.inflight
+                    .swap(false, std::sync::atomic::Ordering::Release);
+                self.confirm_order();
+                return ();
+            }
+            Err(e) => match &e {
+                ExchangeError::Rejected { code, msg } => {
                     match code {
                         -5022 => {
                             self.order = None;
@@ -453,8 +1290,16 @@ impl SymbolTrader {
                         -2022 => {
                             // "reduce only is rejected
                             //"Quantity less than or equal to zero."
-                            dbg!(self.get_remaining_notional(), self.position.notional);
-                            match self.position.reduce_only {
+                            dbg!(
+                                self.get_remaining_qty(),
+                                self.position.notional,
+                                self.position.qty
+                            );
+                            let timestamp = self.get_price_info().timestamp;
+                            let reduce_only = self.position.reduce_only;
+                            match reduce_only {
+                                // already flat, the opening leg is next
+                                true if self.next_leg(timestamp) => Ok(()),
                                 true => {
                                     self.set_filled();
                                     println!(
@@ -464,9 +1309,8 @@ impl SymbolTrader {
                                     Err(e)
                                 }
                                 false => {
-                                    dbg!(code);
-                                    dbg!(msg);
-                                    panic!("shouldnt happen");
+                                    self.stop_on_error(&e);
+                                    Err(e)
                                 }
                             }
                         }
@@ -477,21 +1321,24 @@ impl SymbolTrader {
                             self.set_filled();
                             Err(e)
                         }
-                        -1102 => {
-                            //"Mandatory parameter 'quantity' was not sent, was empty/null, or malformed."
-                            dbg!(code);
-                            dbg!(msg);
-                            panic!("bug");
-                        }
+                        // e.g. -1102 "Mandatory parameter 'quantity' was not sent, was empty/null,
+                        // or malformed."
                         _ => {
-                            dbg!(code);
-                            dbg!(msg);
-                            todo!("unhandled error")
+                            self.stop_on_error(&e);
+                            Err(e)
                         }
                     }
                 }
+                // an answer that couldnt be read, looked up like a timeout
                 _ => {
-                    panic!("the lib has a bug");
+                    println!("{} placing failed, {}", self.position.symbol, e);
+                    self.unconfirmed = Some((client_order_id.to_string(), px, urgency));
+                    self.confirm_attempts = 0;
+                    self.confirm_after = 0;
+                    self.inflight
+                        .swap(false, std::sync::atomic::Ordering::Release);
+                    self.confirm_order();
+                    return ();
                 }
             },
         };
@@ -500,31 +1347,277 @@ impl SymbolTrader {
             .swap(success.is_err(), std::sync::atomic::Ordering::Release);
     }
 
+    fn confirm_order(&mut self) {
+        if self.inflight.load(std::sync::atomic::Ordering::Acquire) {
+            return ();
+        }
+        self.inflight
+            .swap(true, std::sync::atomic::Ordering::Release);
+        let (client_order_id, px, urgency) = self.unconfirmed.clone().unwrap();
+        match self
+            .exchange
+            .query_order(&self.position.symbol, &client_order_id)
+        {
+            Ok(ack) => {
+                self.unconfirmed = None;
+                match ack.status.as_str() {
+                    "NEW" | "PARTIALLY_FILLED" => {
+                        let placed_at = self.get_price_info().timestamp;
+                        self.order = Some(Order::new(ack, px, urgency, placed_at));
+                    }
+                    // filled or gone already, the fills come through the user stream
+                    _ => {}
+                }
+            }
+            Err(e) => match &e {
+                ExchangeError::Rejected { code: -2013, .. } => {
+                    // "Order does not exist.", the order may still be on its way
+                    self.confirm_attempts += 1;
+                    match self.confirm_attempts >= CONFIRM_ATTEMPTS {
+                        true => {
+                            // safe to place again
+                            self.unconfirmed = None;
+                        }
+                        false => {
+                            let backoff = CONFIRM_BACKOFF_MS << (self.confirm_attempts - 1);
+                            self.confirm_after = self.get_price_info().timestamp + backoff;
+                        }
+                    }
+                }
+                _ => {
+                    println!("couldnt confirm {}, retrying", client_order_id);
+                }
+            },
+        };
+        self.inflight
+            .swap(false, std::sync::atomic::Ordering::Release);
+    }
+
+    pub fn shutdown(&mut self) {
+        // called from the main thread on interrupt, the stream keeps running for the final events
+        self.stopped = true;
+        if self.unconfirmed.is_some() {
+            self.inflight
+                .swap(false, std::sync::atomic::Ordering::Release);
+            // no more ticks to wait for, the backoff is slept here
+            for attempt in 0..CONFIRM_ATTEMPTS {
+                if attempt > 0 {
+                    thread::sleep(Duration::from_millis(CONFIRM_BACKOFF_MS << (attempt - 1)));
+                }
+                self.confirm_order();
+                if self.unconfirmed.is_none() {
+                    break;
+                }
+            }
+        }
+        self.cancel_on_shutdown();
+    }
+
+    pub fn resync_order(&mut self) {
+        // execution reports sent while the user stream was down are lost, the fills of the resting
+        // order are caught up from a lookup. a resting limit order fills at its price
+        let order = match self.order.clone() {
+            Some(order) => order,
+            None => return,
+        };
+        let ack = match self
+            .exchange
+            .query_order(&self.position.symbol, &order.ack.client_order_id)
+        {
+            Ok(ack) => ack,
+            Err(e) => {
+                println!(
+                    "couldnt resync {} {}, {}",
+                    self.position.symbol, order.ack.client_order_id, e
+                );
+                return;
+            }
+        };
+        let known: f64 = self
+            .get_fills_for_order(ack.order_id)
+            .iter()
+            .map(|trade| trade.qty)
+            .sum();
+        let missed = self.rules.round_qty(ack.executed_qty - known);
+        if missed > 0.0 {
+            println!(
+                "{} caught up {} missed fills of {}",
+                self.position.symbol, missed, ack.client_order_id
+            );
+            self.on_trade_update(OrderEvent {
+                symbol: self.position.symbol.to_string(),
+                order_id: ack.order_id,
+                execution_type: "TRADE".to_string(),
+                order_status: ack.status.to_string(),
+                last_filled_qty: missed,
+                last_filled_px: ack.price,
+                average_px: ack.price,
+                event_time: ack.update_time,
+                transaction_time: ack.update_time,
+            });
+        }
+        match ack.status.as_str() {
+            "NEW" | "PARTIALLY_FILLED" => {}
+            _ => {
+                self.order = None;
+                self.stale_since = None;
+            }
+        }
+    }
+
+    fn lookup_order(&mut self) {
+        // a cancel or modify timed out, the resting order is looked up by its client order id
+        let order = match self.order.clone() {
+            Some(order) => order,
+            None => return,
+        };
+        match self
+            .exchange
+            .query_order(&self.position.symbol, &order.ack.client_order_id)
+        {
+            Ok(ack) => match ack.status.as_str() {
+                "NEW" | "PARTIALLY_FILLED" => {
+                    let px = ack.price;
+                    self.order = Some(Order::new(ack, px, order.urgency, order.placed_at));
+                }
+                // cancelled, filled or expired, the fills come through the user stream
+                _ => {
+                    self.order = None;
+                    self.stale_since = None;
+                }
+            },
+            // still resting as far as we know, the next tick tries again
+            Err(e) => println!(
+                "couldnt look up {} {}, {}",
+                self.position.symbol, order.ack.client_order_id, e
+            ),
+        }
+    }
+
+    fn cancel_on_shutdown(&mut self) {
+        // synchronous and regardless of inflight, the batch flusher may not run anymore
+        let client_order_id = match &self.order {
+            Some(order) => order.ack.client_order_id.to_string(),
+            None => return,
+        };
+        match self
+            .exchange
+            .cancel_order(&self.position.symbol, &client_order_id)
+        {
+            Ok(_) => {
+                self.order = None;
+                self.cancels += 1;
+            }
+            Err(e) => match &e {
+                ExchangeError::Rejected { code: -2011, .. } => {
+                    self.order = None;
+                }
+                _ => {
+                    println!(
+                        "{} couldnt cancel {} on shutdown, {}",
+                        self.position.symbol, client_order_id, e
+                    );
+                }
+            },
+        }
+    }
+
+    fn get_status(&self) -> String {
+        let aborted = self
+            .guard_events
+            .iter()
+            .any(|(event, _)| event == "aborted");
+        let has_fills = self.get_matched_qty().unwrap_or(0.0) != 0.0;
+        match (
+            &self.validation,
+            self.filled && !aborted && self.error.is_none(),
+            has_fills,
+        ) {
+            (Some(Validation::Accepted), _, _) => "accepted",
+            (Some(Validation::Rejected(_)), _, _) => "rejected",
+            (Some(Validation::Unsupported), _, _) => "unsupported",
+            (None, true, _) => "filled",
+            (None, false, true) => "partially_filled",
+            (None, false, false) => "unfilled",
+        }
+        .to_string()
+    }
+
+    fn on_rule_violation(&mut self, violation: RuleViolation) -> Result<(), RuleViolation> {
+        // checked before the order is sent, mirrors the -4164 handling below
+        match violation {
+            RuleViolation::PriceOutOfRange { px } => {
+                println!(
+                    "{} px {} is outside the price filter, skipping",
+                    self.position.symbol, px
+                );
+                Ok(())
+            }
+            RuleViolation::QtyBelowMin { .. } | RuleViolation::NotionalBelowMin { .. } => {
+                let remaining_qty = self.get_remaining_qty();
+                let working_qty = self.get_working_qty();
+                match self.rules.round_qty(remaining_qty).abs()
+                    > self.rules.round_qty(working_qty).abs()
+                {
+                    true => {
+                        // the rest is held back by the twap schedule or child cap
+                        Ok(())
+                    }
+                    false => {
+                        println!("{} sz is too small, consider filled", self.position.symbol);
+                        dbg!(&violation);
+                        self.set_filled();
+                        Err(violation)
+                    }
+                }
+            }
+        }
+    }
+
+    fn stop_on_error(&mut self, e: &ExchangeError) {
+        // an answer there is no way around, this symbol is given up on and the others keep going.
+        // the resting order is pulled and the error ends up in the summary
+        println!("{} stopping, {}", self.position.symbol, e);
+        self.error = Some(e.to_string());
+        self.stopped = true;
+        self.cancel_on_shutdown();
+        // nothing left to wait for, get_status doesn't take it for a fill
+        self.filled = true;
+    }
+
     fn set_filled(&mut self) {
         println!("fully filled, exiting for {:#?}", self.position.symbol);
         self.filled = true;
     }
 
-    pub fn on_trade_update(&mut self, event: OrderTradeEvent) {
-        self.inflight
-            .swap(true, std::sync::atomic::Ordering::Release);
+    pub fn on_trade_update(&mut self, event: OrderEvent) {
+        // leaves inflight alone, a batched placement or cancel may still be queued
         match (
-            OrderStatus::from(event.order.execution_type.as_str()),
-            OrderStatus::from(event.order.order_status.as_str()),
+            OrderStatus::from(event.execution_type.as_str()),
+            OrderStatus::from(event.order_status.as_str()),
         ) {
             (OrderStatus::New, OrderStatus::New) => {}
+            // the modify response already updated the resting order
+            (OrderStatus::Amendment, _) => {}
             (OrderStatus::Cancelled, OrderStatus::Cancelled) => {}
+            (OrderStatus::Expired, OrderStatus::Expired) => {
+                // unfilled remainder of an IOC/market sweep or a GTX order that would have crossed
+                if let Some(resting_order) = self.order.as_ref() {
+                    if resting_order.ack.order_id == event.order_id {
+                        self.order = None;
+                    }
+                }
+            }
             _ => {
                 self.trade_events.push(event.clone());
 
                 if let Some(resting_order) = self.order.as_ref() {
-                    match resting_order.transaction.order_id == event.order.order_id {
+                    match resting_order.ack.order_id == event.order_id {
                         true => {
                             //only handle a fully filled order
                             //partial fills means the order is not stale and will be processed by other
                             // event handlers
-                            if OrderStatus::from(event.order.order_status.as_str())
-                                == OrderStatus::Filled
+                            if OrderStatus::from(event.order_status.as_str()) == OrderStatus::Filled
                             {
                                 self.order = None;
                             }
@@ -534,20 +1627,15 @@ impl SymbolTrader {
                 }
             }
         }
-        self.inflight
-            .swap(false, std::sync::atomic::Ordering::Release);
     }
 }
 
-pub fn check_if_filled(
-    traders: HashMap<String, Arc<Mutex<SymbolTrader>>>,
-    running: Arc<AtomicBool>,
-) {
+pub fn check_if_filled(traders: Vec<Arc<Mutex<SymbolTrader>>>, running: Arc<AtomicBool>) {
     thread::spawn(move || {
         while running.load(std::sync::atomic::Ordering::Acquire) {
             thread::sleep(Duration::from_millis(100)); //no need for low latency here, sleep to reduce cpu use
             {
-                let are_filled: bool = traders.iter().all(|x| x.1.lock().unwrap().filled);
+                let are_filled: bool = traders.iter().all(|x| x.lock().unwrap().filled);
                 if are_filled {
                     println!("all filled, exiting ...");
                     running.swap(false, std::sync::atomic::Ordering::Release);
@@ -574,6 +1662,66 @@ pub struct TraderSummary {
     pub mids: Vec<Mid>,
     #[pyo3(get, set)]
     pub trades: Vec<Trade>,
+    #[pyo3(get, set)]
+    pub urgency_changes: Vec<(String, u64)>,
+    #[pyo3(get, set)]
+    pub participation_rate: Option<f64>,
+    #[pyo3(get, set)]
+    pub guard_events: Vec<(String, u64)>,
+    #[pyo3(get, set)]
+    pub cancels: u32,
+    #[pyo3(get, set)]
+    pub replaces: u32,
+    #[pyo3(get, set)]
+    pub amends: u32,
+    #[pyo3(get, set)]
+    pub leftovers: Vec<(String, String)>,
+    // "filled", "partially_filled" or "unfilled", the latter two after an interrupt, abort or
+    // error. "accepted", "rejected" or "unsupported" (coin-m) in a validation run
+    #[pyo3(get, set)]
+    pub status: String,
+    // why a validation run rejected the order, or the error that stopped the trader
+    #[pyo3(get, set)]
+    pub error: Option<String>,
+    #[pyo3(get, set)]
+    pub start_qty: Option<f64>,
+    // (qty, reduce_only, completed_at) of the close/open legs of a flip
+    #[pyo3(get, set)]
+    pub legs: Vec<(f64, bool, Option<u64>)>,
+}
+
+impl From<&SymbolTrader> for TraderSummary {
+    fn from(x: &SymbolTrader) -> Self {
+        TraderSummary {
+            position: x.position.clone(),
+            avg_entry: x.get_avg_entry(),
+            price_at_start: x.price_at_start,
+            matched_qty: x.get_matched_qty(),
+            completed_at: x.get_last_ts(),
+            trades: x.get_trades(),
+            mids: x.mids.clone(),
+            urgency_changes: x.urgency_changes.clone(),
+            participation_rate: x.participation.as_ref().and_then(|participation| {
+                participation.realized_rate(x.get_matched_qty().unwrap_or(0.0))
+            }),
+            guard_events: x.guard_events.clone(),
+            cancels: x.cancels,
+            replaces: x.replaces,
+            amends: x.amends,
+            leftovers: x.leftovers.clone(),
+            status: x.get_status(),
+            error: match &x.validation {
+                Some(Validation::Rejected(e)) => Some(e.to_string()),
+                _ => x.error.clone(),
+            },
+            start_qty: x.start_qty,
+            legs: x
+                .legs
+                .iter()
+                .map(|leg| (leg.qty, leg.reduce_only, leg.completed_at))
+                .collect(),
+        }
+    }
 }
 
 #[pymethods]
@@ -601,70 +1749,401 @@ impl TraderSummary {
                     .collect::<Vec<(f64, u64)>>()
                     .to_object(py),
             ),
+            ("urgency_changes", self.urgency_changes.to_object(py)),
+            ("participation_rate", self.participation_rate.to_object(py)),
+            ("guard_events", self.guard_events.to_object(py)),
+            ("cancels", self.cancels.to_object(py)),
+            ("replaces", self.replaces.to_object(py)),
+            ("amends", self.amends.to_object(py)),
+            ("leftovers", self.leftovers.to_object(py)),
+            ("status", self.status.to_object(py)),
+            ("error", self.error.to_object(py)),
+            ("start_qty", self.start_qty.to_object(py)),
+            ("legs", self.legs.to_object(py)),
         ];
         key_vals.into_py(py)
     }
 }
 
+// how long to keep listening for trade events after cancelling on interrupt
+const FINAL_EVENTS_WAIT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunMode {
+    Live,
+    // live public data, the orders fill against a SimExchange per venue started from the
+    // current books. one-way mode, no batching, and the account's positions are not looked at
+    Paper,
+    // the first order of each position, and of a flip's opening leg, is built from the current
+    // book and the account's position, then sent to the venue's test endpoint. nothing is placed
+    // or cancelled
+    Validate,
+}
+
+impl RunMode {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "live" => Ok(RunMode::Live),
+            "paper" => Ok(RunMode::Paper),
+            "validate" => Ok(RunMode::Validate),
+            _ => Err(format!("Invalid run mode {:?}", s)),
+        }
+    }
+}
+
+fn handle_sigterm(py: Python) -> Option<PyObject> {
+    // python only turns SIGINT into KeyboardInterrupt, route SIGTERM the same way for the run.
+    // returns the previous handler, None if it couldn't be installed (e.g. not the main thread)
+    let signal = py.import_bound("signal").ok()?;
+    let sigterm = signal.getattr("SIGTERM").ok()?;
+    let previous = signal.call_method1("getsignal", (&sigterm,)).ok()?;
+    let handler = signal.getattr("default_int_handler").ok()?;
+    signal.call_method1("signal", (&sigterm, handler)).ok()?;
+    match previous.is_none() {
+        // not installed from python
+        true => Some(signal.getattr("SIG_DFL").ok()?.unbind()),
+        false => Some(previous.unbind()),
+    }
+}
+
+fn restore_sigterm(py: Python, previous: Option<PyObject>) {
+    if let Some(previous) = previous {
+        let signal = py.import_bound("signal").unwrap();
+        signal
+            .call_method1("signal", (signal.getattr("SIGTERM").unwrap(), previous))
+            .unwrap();
+    }
+}
+
 #[pyfunction]
-pub fn run_binance(positions: Vec<Position>) -> Vec<TraderSummary> {
+#[pyo3(signature = (positions, batch = false, leftovers = "cancel", record = None, mode = "live"))]
+pub fn run_binance(
+    py: Python,
+    positions: Vec<Position>,
+    batch: bool,
+    leftovers: &str,
+    record: Option<&str>,
+    mode: &str,
+) -> PyResult<Vec<TraderSummary>> {
+    let mode = RunMode::parse(mode).map_err(PyValueError::new_err)?;
+    LeftoverAction::parse(leftovers).map_err(PyValueError::new_err)?;
+    for position in positions.iter() {
+        position.validate().map_err(PyValueError::new_err)?;
+    }
+    let previous_sigterm = handle_sigterm(py);
+    // the gil is released for the whole run, the watch loop takes it back to check for signals
+    let summary = py.allow_threads(|| {
+        run_positions(positions, batch, leftovers, record, mode, || {
+            Python::with_gil(|py| py.check_signals().is_err())
+        })
+    });
+    restore_sigterm(py, previous_sigterm);
+    summary.map_err(PyValueError::new_err)
+}
+
+pub fn run_positions(
+    positions: Vec<Position>,
+    batch: bool,
+    leftovers: &str,
+    record: Option<&str>,
+    mode: RunMode,
+    is_interrupted: impl Fn() -> bool,
+) -> Result<Vec<TraderSummary>, String> {
     let keep_running = Arc::new(AtomicBool::new(true));
+    // in the client ids, orders of ours from runs started after this are from a run going on alongside
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
 
+    // one limiter per venue, the weight and order counts are per account/ip and venue
+    let mut venues: Vec<Venue> = vec![];
+    for position in positions.iter() {
+        if !venues.contains(&position.venue()) {
+            venues.push(position.venue());
+        }
+    }
+    let limiters: HashMap<Venue, Arc<RateLimiter>> = venues
+        .iter()
+        .map(|venue| (*venue, Arc::new(RateLimiter::new(venue.rate_limits()))))
+        .collect();
+
+    // usd-m and coin-m each have their own position mode
+    let hedge_modes: HashMap<Venue, bool> = venues
+        .iter()
+        .filter(|venue| venue.has_positions())
+        .map(|venue| {
+            let hedge_mode = match mode {
+                RunMode::Live | RunMode::Validate => {
+                    Rest::new(*venue, Arc::clone(&limiters[venue]))
+                        .is_hedge_mode()
+                        .expect("couldnt fetch the position mode")
+                }
+                RunMode::Paper => false,
+            };
+            (*venue, hedge_mode)
+        })
+        .collect();
     //get tick size etc
-    let (account, general) = get_futures_account();
-    let exchange_info = general.exchange_info().unwrap();
+    let exchange_info = match limiters.contains_key(&Venue::UsdM) {
+        true => {
+            let (_, general) = get_futures_account();
+            Some(general.exchange_info().unwrap())
+        }
+        false => None,
+    };
+    // baskets can coalesce their rest calls into batchOrders, usd-m only
+    let batch = match (batch, mode, limiters.get(&Venue::UsdM)) {
+        (true, RunMode::Live, Some(limiter)) => Some(Arc::new(BatchCoordinator::new(Rest::new(
+            Venue::UsdM,
+            Arc::clone(limiter),
+        )))),
+        _ => None,
+    };
+
+    let run_id = new_run_id(started_at);
+    println!("run id {}", run_id);
+    // the streams as received and our order events, to replay with run_backtest
+    let recorder = record.map(|path| Arc::new(Recorder::create(path)));
+
+    let rules: Vec<SymbolRules> = positions
+        .iter()
+        .map(|x| match &exchange_info {
+            Some(exchange_info) if x.venue() == Venue::UsdM => {
+                let info = exchange_info
+                    .symbols
+                    .iter()
+                    .find(|y| y.symbol == x.symbol)
+                    .expect(&format!("{} not found in exchange info", &x.symbol));
+                SymbolRules::from(info)
+            }
+            _ => Rest::new(x.venue(), Arc::clone(&limiters[&x.venue()]))
+                .symbol_rules(&x.symbol)
+                .expect("couldnt fetch the exchange info"),
+        })
+        .collect();
+    let sims: HashMap<Venue, Arc<SimExchange>> = match mode {
+        RunMode::Live | RunMode::Validate => HashMap::new(),
+        RunMode::Paper => venues
+            .iter()
+            .map(|venue| {
+                let venue_rules = positions
+                    .iter()
+                    .zip(rules.iter())
+                    .filter(|(x, _)| x.venue() == *venue)
+                    .map(|(x, rules)| (x.symbol.to_string(), rules.clone()))
+                    .collect::<HashMap<String, SymbolRules>>();
+                // the traders mark their positions and the first orders against these
+                let rest = Rest::new(*venue, Arc::clone(&limiters[venue]));
+                let sim = SimExchange::new(venue_rules.clone(), FillModel::default());
+                for symbol in venue_rules.keys() {
+                    sim.on_book(&rest.book(symbol).expect("couldnt fetch the book"));
+                }
+                (*venue, Arc::new(sim))
+            })
+            .collect(),
+    };
 
     //init traders...
-    let traders: HashMap<String, Arc<Mutex<SymbolTrader>>> = positions
+    let traders: Vec<Arc<Mutex<SymbolTrader>>> = positions
         .iter()
-        .map(|x| {
-            let info = exchange_info
-                .symbols
-                .iter()
-                .find(|y| y.symbol == x.symbol)
-                .expect(&format!("{} not found in exchange info", &x.symbol));
+        .enumerate()
+        .map(|(idx, x)| {
+            let venue = x.venue();
+            let exchange: Arc<dyn Exchange> = match mode {
+                RunMode::Live | RunMode::Validate => {
+                    Arc::new(Rest::new(venue, Arc::clone(&limiters[&venue])))
+                }
+                RunMode::Paper => Arc::clone(&sims[&venue]) as Arc<dyn Exchange>,
+            };
+            let batch = match venue {
+                Venue::UsdM => batch.clone(),
+                Venue::Spot | Venue::CoinM => None,
+            };
 
-            let key = x.symbol.to_string();
-            (
-                key,
-                Arc::new(Mutex::new(SymbolTrader::new(
-                    x.clone(),
-                    Arc::clone(&keep_running),
-                    info.clone(),
-                    account.clone(),
-                ))),
-            )
+            Arc::new(Mutex::new(SymbolTrader::new(
+                x.clone(),
+                Arc::clone(&keep_running),
+                rules[idx].clone(),
+                exchange,
+                batch,
+                ClientOrderIds::new(&run_id, idx),
+            )))
         })
         .collect();
+    let venue_traders = |venue: Venue| -> HashMap<String, Arc<Mutex<SymbolTrader>>> {
+        traders
+            .iter()
+            .filter(|trader| trader.lock().unwrap().venue == venue)
+            .map(|trader| {
+                let key = trader.lock().unwrap().position.symbol.to_string();
+                (key, Arc::clone(trader))
+            })
+            .collect()
+    };
+
+    // "cancel", "adopt" or "ignore" orders with our client id prefix left by a previous run
+    let leftover_policy = LeftoverAction::from(leftovers);
+    // every position is checked against the account before anything is cancelled or placed
+    for trader in traders.iter() {
+        let mut trader = trader.lock().unwrap();
+        let hedge_mode = hedge_modes.get(&trader.venue).copied().unwrap_or(false);
+        trader.set_position_mode(hedge_mode)?;
+        trader.resolve_position()?;
+    }
+    for trader in traders.iter() {
+        let mut trader = trader.lock().unwrap();
+        if let Some(recorder) = &recorder {
+            recorder.rules(&trader.position.symbol, &trader.rules);
+        }
+        match mode {
+            // leftovers are left alone
+            RunMode::Validate => {}
+            RunMode::Live | RunMode::Paper => trader.reconcile(leftover_policy, started_at),
+        }
+    }
+    if mode == RunMode::Validate {
+        for trader in traders.iter() {
+            let mut trader = trader.lock().unwrap();
+            let book = Rest::new(trader.venue, Arc::clone(&limiters[&trader.venue]))
+                .book(&trader.position.symbol)
+                .expect("couldnt fetch the book");
+            trader.validate_order(book);
+        }
+        return Ok(traders
+            .iter()
+            .map(|x| TraderSummary::from(&*x.lock().unwrap()))
+            .collect());
+    }
 
     let summary = traders.clone();
     check_if_filled(traders.clone(), Arc::clone(&keep_running));
-    init_stream(traders, Arc::clone(&keep_running));
-    let summary: Vec<TraderSummary> = summary
-        .values()
-        .map(|x| x.lock().unwrap())
-        .map(|x| {
-            let summary = TraderSummary {
-                position: x.position.clone(),
-                avg_entry: x.get_avg_entry(),
-                price_at_start: x.price_at_start,
-                matched_qty: x.get_matched_qty(),
-                completed_at: x.get_last_ts(),
-                trades: x.get_trades(),
-                mids: x.mids.clone(),
-            };
+    if let Some(batch) = batch {
+        start_flusher(batch, venue_traders(Venue::UsdM), Arc::clone(&keep_running));
+    }
 
-            summary
+    // each venue streams on its own thread so this one can keep an eye on ctrl-c / SIGTERM
+    let streams: Vec<thread::JoinHandle<()>> = venues
+        .iter()
+        .map(|venue| {
+            let venue = *venue;
+            let traders = venue_traders(venue);
+            let keep_running = Arc::clone(&keep_running);
+            let rest = Rest::new(venue, Arc::clone(&limiters[&venue]));
+            let recorder = recorder.clone();
+            match (mode, venue) {
+                (RunMode::Validate, _) => unreachable!("validation runs return before streaming"),
+                (RunMode::Live, Venue::UsdM | Venue::CoinM) => {
+                    thread::spawn(move || init_stream(traders, keep_running, rest, recorder))
+                }
+                (RunMode::Live, Venue::Spot) => {
+                    thread::spawn(move || init_spot_stream(traders, keep_running, recorder))
+                }
+                (RunMode::Paper, _) => {
+                    let sim = Arc::clone(&sims[&venue]);
+                    thread::spawn(move || {
+                        init_paper_stream(traders, keep_running, venue, sim, recorder)
+                    })
+                }
+            }
         })
         .collect();
+    let mut interrupted = false;
+    // a stream only ends by itself once everything is filled, or when it crashed
+    while !streams.iter().any(|stream| stream.is_finished()) {
+        thread::sleep(Duration::from_millis(100));
+        if is_interrupted() {
+            interrupted = true;
+            break;
+        }
+    }
+
+    if interrupted {
+        println!("interrupted, cancelling resting orders ...");
+        for trader in traders.iter() {
+            trader.lock().unwrap().shutdown();
+        }
+        // fills that raced the cancels still come through the user stream
+        thread::sleep(FINAL_EVENTS_WAIT);
+    }
+    keep_running.swap(false, std::sync::atomic::Ordering::Release);
+    let mut panics = streams
+        .into_iter()
+        .filter_map(|stream| stream.join().err())
+        .collect::<Vec<_>>();
+    if !panics.is_empty() {
+        // don't leave orders in the book behind a crash
+        for trader in traders.iter() {
+            trader
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .shutdown();
+        }
+        std::panic::resume_unwind(panics.remove(0));
+    }
+    if let Some(recorder) = &recorder {
+        recorder.finish();
+    }
+
+    let summary: Vec<TraderSummary> = summary
+        .iter()
+        .map(|x| TraderSummary::from(&*x.lock().unwrap()))
+        .collect();
 
     println!("done");
-    summary
+    Ok(summary)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exchange::Level;
+
+    fn book(event_time: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Book {
+        let levels = |levels: &[(f64, f64)]| -> Vec<Level> {
+            levels
+                .iter()
+                .map(|(px, qty)| Level { px: *px, qty: *qty })
+                .collect()
+        };
+        Book {
+            symbol: "BTCUSDT".to_string(),
+            event_time: event_time,
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    fn rules() -> SymbolRules {
+        SymbolRules {
+            tick_size: 0.1,
+            min_price: 1.0,
+            max_price: 1000000.0,
+            step_size: 0.001,
+            min_qty: 0.001,
+            max_qty: 1000.0,
+            min_notional: 5.0,
+            contract_size: None,
+        }
+    }
+
+    fn position(qty: f64) -> Position {
+        Position {
+            symbol: "BTCUSDT".to_string(),
+            notional: 0.0,
+            reduce_only: false,
+            sz_type: "q".to_string(),
+            qty: qty,
+            ..Default::default()
+        }
+    }
+
+    fn exchange() -> Arc<SimExchange> {
+        Arc::new(SimExchange::new(
+            HashMap::from([("BTCUSDT".to_string(), rules())]),
+            FillModel::default(),
+        ))
+    }
 
     #[test]
     fn test_binance_single_position() {
@@ -673,8 +2152,180 @@ mod tests {
             symbol: "BTCUSDT".parse().unwrap(),
             notional: 10000.0,
             reduce_only: false,
+            ..Default::default()
         }];
 
-        run_binance(positions);
+        run_positions(positions, false, "cancel", None, RunMode::Live, || false).unwrap();
+    }
+
+    #[test]
+    fn test_validate_order() {
+        let exchange = exchange();
+        exchange.on_book(&book(0, &[(100.0, 1.0)], &[(100.1, 5.0)]));
+        let validate = |position: Position| {
+            let mut trader = SymbolTrader::new(
+                position,
+                Arc::new(AtomicBool::new(true)),
+                exchange.rules("BTCUSDT"),
+                Arc::clone(&exchange) as Arc<dyn Exchange>,
+                None,
+                ClientOrderIds::new("test", 0),
+            );
+            trader.set_position_mode(false).unwrap();
+            trader.resolve_position().unwrap();
+            trader.validate_order(book(0, &[(100.0, 1.0)], &[(100.1, 5.0)]));
+            TraderSummary::from(&trader)
+        };
+        let summary = validate(position(1.0));
+        assert_eq!(summary.status, "accepted");
+        assert_eq!(summary.error, None);
+        // nothing rests after a validation
+        assert!(exchange.open_orders("BTCUSDT").unwrap().is_empty());
+
+        // nothing to reduce
+        let summary = validate(Position {
+            reduce_only: true,
+            ..position(-1.0)
+        });
+        assert_eq!(summary.status, "rejected");
+        assert_eq!(
+            summary.error,
+            Some("rejected with -2022 ReduceOnly Order is rejected.".to_string())
+        );
+
+        // the filters are checked by the venue, the error is the one it sends
+        let summary = validate(position(0.01));
+        assert_eq!(summary.status, "rejected");
+        assert!(summary.error.unwrap().starts_with("rejected with -4164"));
+
+        // a flip's opening leg is checked too, too small here once the close is done
+        exchange.set_position("BTCUSDT", -1.0);
+        let summary = validate(position(1.01));
+        assert_eq!(summary.status, "rejected");
+        assert!(summary.error.unwrap().starts_with("rejected with -4164"));
+        let summary = validate(position(2.0));
+        assert_eq!(summary.status, "accepted");
+
+        // nothing to send
+        let summary = validate(Position {
+            target: true,
+            ..position(-1.0)
+        });
+        assert_eq!(summary.status, "accepted");
+
+        // coin-m has no test endpoint
+        let summary = validate(Position {
+            venue: "coinm".to_string(),
+            ..position(1.0)
+        });
+        assert_eq!(summary.status, "unsupported");
+        assert_eq!(summary.error, None);
+    }
+
+    #[test]
+    fn test_coinm_avg_entry() {
+        // 100 usd contracts, 1 at 50000 and 1 at 40000 buy 0.0045 btc for 200 usd
+        let rules = SymbolRules {
+            contract_size: Some(100.0),
+            ..rules()
+        };
+        let position = Position {
+            symbol: "BTCUSD_PERP".to_string(),
+            venue: "coinm".to_string(),
+            ..position(2.0)
+        };
+        let mut trader = SymbolTrader::new(
+            position,
+            Arc::new(AtomicBool::new(true)),
+            rules,
+            exchange() as Arc<dyn Exchange>,
+            None,
+            ClientOrderIds::new("test", 0),
+        );
+        for (order_id, px) in [(1, 50000.0), (2, 40000.0)] {
+            trader.on_trade_update(OrderEvent {
+                symbol: "BTCUSD_PERP".to_string(),
+                order_id: order_id,
+                execution_type: "TRADE".to_string(),
+                order_status: "FILLED".to_string(),
+                last_filled_qty: 1.0,
+                last_filled_px: px,
+                average_px: px,
+                event_time: 0,
+                transaction_time: 0,
+            });
+        }
+        let avg_entry = TraderSummary::from(&trader).avg_entry.unwrap();
+        assert!((avg_entry - 200.0 / 0.0045).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_unknown_status_backoff() {
+        // a timed out placement is looked up a few times before it is placed again
+        let exchange = exchange();
+        let mut trader = SymbolTrader::new(
+            position(1.0),
+            Arc::new(AtomicBool::new(true)),
+            exchange.rules("BTCUSDT"),
+            Arc::clone(&exchange) as Arc<dyn Exchange>,
+            None,
+            ClientOrderIds::new("test", 0),
+        );
+        exchange.fail_next(ExchangeError::UnknownStatus);
+        let mut tick = |event_time: u64| {
+            let book = book(event_time, &[(100.0, 1.0)], &[(100.1, 5.0)]);
+            exchange.on_book(&book);
+            trader.handle_price_event(book);
+            (trader.unconfirmed.is_some(), trader.order.is_some())
+        };
+        assert_eq!(tick(0), (true, false));
+        assert_eq!(tick(100), (true, false));
+        assert_eq!(tick(500), (true, false));
+        assert_eq!(tick(1400), (true, false));
+        // the third "Order does not exist." gives up on it
+        assert_eq!(tick(1500), (false, false));
+        assert_eq!(tick(1600), (false, true));
+        assert_eq!(exchange.open_orders("BTCUSDT").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_order_errors() {
+        // an unreadable answer is looked up like a timeout, an unknown rejection stops the symbol
+        let exchange = exchange();
+        let mut trader = SymbolTrader::new(
+            position(1.0),
+            Arc::new(AtomicBool::new(true)),
+            exchange.rules("BTCUSDT"),
+            Arc::clone(&exchange) as Arc<dyn Exchange>,
+            None,
+            ClientOrderIds::new("test", 0),
+        );
+        let book = book(0, &[(100.0, 1.0)], &[(100.1, 5.0)]);
+        exchange.on_book(&book);
+        exchange.fail_next(ExchangeError::Other("expected value at line 1".to_string()));
+        trader.handle_price_event(book.clone());
+        assert!(trader.unconfirmed.is_some());
+        assert!(!trader.stopped);
+
+        let mut trader = SymbolTrader::new(
+            position(1.0),
+            Arc::new(AtomicBool::new(true)),
+            exchange.rules("BTCUSDT"),
+            Arc::clone(&exchange) as Arc<dyn Exchange>,
+            None,
+            ClientOrderIds::new("test", 1),
+        );
+        exchange.fail_next(ExchangeError::Rejected {
+            code: -1102,
+            msg: "Mandatory parameter 'quantity' was not sent.".to_string(),
+        });
+        trader.handle_price_event(book);
+        assert!(trader.stopped && trader.filled);
+        let summary = TraderSummary::from(&trader);
+        assert_eq!(summary.status, "unfilled");
+        assert_eq!(
+            summary.error.unwrap(),
+            "rejected with -1102 Mandatory parameter 'quantity' was not sent."
+        );
     }
 }
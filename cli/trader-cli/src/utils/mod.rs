@@ -19,6 +19,20 @@ pub fn get_futures_account() -> (FuturesAccount, FuturesGeneral) {
 
 pub trait Round {
     fn round_to_n(self, decimal_places: i32) -> f64;
+    fn round_to_step(self, step: f64) -> f64;
+    fn floor_to_step(self, step: f64) -> f64;
+    fn ceil_to_step(self, step: f64) -> f64;
+}
+
+pub fn step_decimals(step: f64) -> i32 {
+    // number of decimals needed to print a tick/step size, e.g. 0.001 -> 3
+    let mut decimals = 0;
+    while decimals < 12
+        && ((step * 10_f64.powi(decimals)).round() - step * 10_f64.powi(decimals)).abs() > 1e-9
+    {
+        decimals += 1;
+    }
+    decimals
 }
 
 impl Round for f64 {
@@ -26,4 +40,17 @@ impl Round for f64 {
         let mult = 10_f64.powi(decimal_places);
         (self * mult).round() / mult
     }
+
+    fn round_to_step(self, step: f64) -> f64 {
+        ((self / step).round() * step).round_to_n(step_decimals(step))
+    }
+
+    fn floor_to_step(self, step: f64) -> f64 {
+        // the epsilon keeps 0.3 / 0.1 = 2.9999999999999996 from flooring to 2
+        ((self / step + 1e-9).floor() * step).round_to_n(step_decimals(step))
+    }
+
+    fn ceil_to_step(self, step: f64) -> f64 {
+        ((self / step - 1e-9).ceil() * step).round_to_n(step_decimals(step))
+    }
 }
@@ -0,0 +1,198 @@
+use binance::config::Config;
+
+use crate::ratelimit::RateLimits;
+
+fn is_testnet(config: &Config) -> bool {
+    config.rest_api_endpoint == Config::testnet().rest_api_endpoint
+}
+
+fn coinm_endpoints(testnet: bool) -> (&'static str, &'static str) {
+    // (rest, websocket), Config only has the usd-m futures ones
+    match testnet {
+        true => (
+            "https://testnet.binancefuture.com",
+            "wss://dstream.binancefuture.com",
+        ),
+        false => ("https://dapi.binance.com", "wss://dstream.binance.com"),
+    }
+}
+
+// where a position is worked, see Position.venue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Venue {
+    UsdM,
+    Spot,
+    CoinM,
+}
+
+impl Venue {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "usdm" => Ok(Venue::UsdM),
+            "spot" => Ok(Venue::Spot),
+            "coinm" => Ok(Venue::CoinM),
+            _ => Err(format!("Invalid venue {:?}", s)),
+        }
+    }
+
+    pub fn from(s: &str) -> Self {
+        Venue::parse(s).unwrap()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "usdm",
+            Venue::Spot => "spot",
+            Venue::CoinM => "coinm",
+        }
+    }
+
+    pub fn rest_endpoint(&self, config: &Config) -> String {
+        match self {
+            Venue::UsdM => config.futures_rest_api_endpoint.to_string(),
+            Venue::Spot => config.rest_api_endpoint.to_string(),
+            Venue::CoinM => coinm_endpoints(is_testnet(config)).0.to_string(),
+        }
+    }
+
+    pub fn ws_endpoint(&self, config: &Config) -> String {
+        match self {
+            Venue::UsdM => config.futures_ws_endpoint.to_string(),
+            Venue::Spot => config.ws_endpoint.to_string(),
+            Venue::CoinM => coinm_endpoints(is_testnet(config)).1.to_string(),
+        }
+    }
+
+    pub fn order_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v1/order",
+            Venue::Spot => "/api/v3/order",
+            Venue::CoinM => "/dapi/v1/order",
+        }
+    }
+
+    // coin-m has no test order endpoint
+    pub fn order_test_path(&self) -> Option<&'static str> {
+        match self {
+            Venue::UsdM => Some("/fapi/v1/order/test"),
+            Venue::Spot => Some("/api/v3/order/test"),
+            Venue::CoinM => None,
+        }
+    }
+
+    pub fn open_orders_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v1/openOrders",
+            Venue::Spot => "/api/v3/openOrders",
+            Venue::CoinM => "/dapi/v1/openOrders",
+        }
+    }
+
+    pub fn exchange_info_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v1/exchangeInfo",
+            Venue::Spot => "/api/v3/exchangeInfo",
+            Venue::CoinM => "/dapi/v1/exchangeInfo",
+        }
+    }
+
+    pub fn depth_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v1/depth",
+            Venue::Spot => "/api/v3/depth",
+            Venue::CoinM => "/dapi/v1/depth",
+        }
+    }
+
+    pub fn position_risk_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v2/positionRisk",
+            Venue::CoinM => "/dapi/v1/positionRisk",
+            Venue::Spot => panic!("spot has no positions"),
+        }
+    }
+
+    pub fn position_mode_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v1/positionSide/dual",
+            Venue::CoinM => "/dapi/v1/positionSide/dual",
+            Venue::Spot => panic!("spot has no positions"),
+        }
+    }
+
+    pub fn batch_orders_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v1/batchOrders",
+            Venue::CoinM => "/dapi/v1/batchOrders",
+            Venue::Spot => panic!("spot has no batch orders"),
+        }
+    }
+
+    pub fn listen_key_path(&self) -> &'static str {
+        match self {
+            Venue::UsdM => "/fapi/v1/listenKey",
+            Venue::Spot => "/api/v3/userDataStream",
+            Venue::CoinM => "/dapi/v1/listenKey",
+        }
+    }
+
+    pub fn rate_limits(&self) -> RateLimits {
+        match self {
+            Venue::UsdM => RateLimits::USDM,
+            Venue::Spot => RateLimits::SPOT,
+            Venue::CoinM => RateLimits::COINM,
+        }
+    }
+
+    // spot has no positions, so no reduce-only, position side, modify or batchOrders
+    pub fn has_positions(&self) -> bool {
+        match self {
+            Venue::UsdM | Venue::CoinM => true,
+            Venue::Spot => false,
+        }
+    }
+}
+
+pub fn normalize_error_code(venue: Venue, code: i16, msg: &str) -> i16 {
+    // spot reports some rejections under generic codes, map them to the futures ones the trader handles
+    match (venue, code) {
+        // "Order would immediately match and take." on a LIMIT_MAKER
+        (Venue::Spot, -2010) if msg.contains("immediately match") => -5022,
+        // "Filter failure: NOTIONAL" / "Filter failure: MIN_NOTIONAL"
+        (Venue::Spot, -1013) if msg.contains("NOTIONAL") => -4164,
+        _ => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_error_code() {
+        assert_eq!(
+            normalize_error_code(
+                Venue::Spot,
+                -2010,
+                "Order would immediately match and take."
+            ),
+            -5022
+        );
+        assert_eq!(
+            normalize_error_code(
+                Venue::Spot,
+                -2010,
+                "Account has insufficient balance for requested action."
+            ),
+            -2010
+        );
+        assert_eq!(
+            normalize_error_code(Venue::Spot, -1013, "Filter failure: NOTIONAL"),
+            -4164
+        );
+        assert_eq!(
+            normalize_error_code(Venue::UsdM, -1013, "Filter failure: NOTIONAL"),
+            -1013
+        );
+    }
+}
@@ -3,6 +3,7 @@ import polars as pl
 import pandas as pd
 from rust_trader import (
     Position as Position,
+    run_backtest as run_rust_trader_backtest,
     run_binance as run_rust_trader_binance,
 )
 from django.conf import settings
@@ -38,21 +39,26 @@ class OrderQuerySet(models.QuerySet):
         )
         return df
 
-    def execute(self):
+    def execute(self, record=None):
         print("executing via rust trader", self)
-        if settings.DRYRUN:
-            print("dry run, quitting bulk rust execute")
-            return
+        # a dry run trades on paper, live books and simulated fills, the account is not touched
+        mode = "paper" if settings.DRYRUN else "live"
         exchange = pd.Series([x.exchange for x in self]).unique()
         assert len(exchange) == 1
         if exchange == settings.BINANCE_FUTURES_STRING:
             result = run_rust_trader_binance(
-                [x.to_rust_trader() for x in self]
+                [x.to_rust_trader() for x in self],
+                batch=len(self) > 1,
+                record=record,
+                mode=mode,
             )
         else:
             raise NotImplementedError("exchange not supported")
 
         result = [dict(x.as_dict()) for x in result]
+        if mode == "paper":
+            # simulated fills are not executions, the order stays unexecuted
+            return result
         for order in self:
             for execute_result in result:
                 if execute_result["symbol"] == order.symbol:
@@ -71,6 +77,40 @@ class OrderQuerySet(models.QuerySet):
             order.save()
         return result
 
+    def validate(self):
+        # every order built as the trader would place it now and sent to
+        # Order.DRYRUN_ORDER_TEST_ENDPOINT instead, nothing is placed.
+        # a rejected order gets binance's error
+        exchange = pd.Series([x.exchange for x in self]).unique()
+        assert len(exchange) == 1
+        if exchange != settings.BINANCE_FUTURES_STRING:
+            raise NotImplementedError("exchange not supported")
+        print("validating against", Order.DRYRUN_ORDER_TEST_ENDPOINT, self)
+        result = run_rust_trader_binance(
+            [x.to_rust_trader() for x in self], mode="validate"
+        )
+        result = [dict(x.as_dict()) for x in result]
+        for order in self:
+            for validate_result in result:
+                if (
+                    validate_result["symbol"] == order.symbol
+                    and validate_result["error"] is not None
+                ):
+                    order.error = validate_result["error"]
+                    order.save()
+        return result
+
+    def backtest(self, path, latency_ms=0, queue_fraction=1.0):
+        # replays a recording instead of trading, nothing is saved.
+        # same dicts as execute returns, to compare with the executed orders
+        result = run_rust_trader_backtest(
+            [x.to_rust_trader() for x in self],
+            path,
+            latency_ms=latency_ms,
+            queue_fraction=queue_fraction,
+        )
+        return [dict(x.as_dict()) for x in result]
+
 
 class OrderManager(models.Manager):
     def get_queryset(self):
@@ -132,20 +172,31 @@ class Order(models.Model):
         return f"{'**ERROR** ' if not not self.error else ''}{self.created_at} {self.exchange} {self.symbol} @ {self.price_expected} -- {self.sz}/{self.sz_type}"
 
     def to_rust_trader(self):
-        assert self.sz_type == "n"
+        assert self.sz_type in [self.SZ_TYPE_NOTIONAL, self.SZ_TYPE_QTY]
         assert self._type == "l"
         assert self.exchange in [
             settings.BINANCE_FUTURES_STRING,
         ]
         if self.exchange == settings.BINANCE_FUTURES_STRING:
-            mult = 1 if not self.liquidated else 2
-            print("mult,self.liquidate,symbol,ex")
-            print(mult, self.liquidated, self.symbol, self.exchange)
-            notional = self.sz * mult
+            if self.liquidated:
+                # flatten whatever the account holds, reduce-only is picked by the trader and in hedge
+                # mode the open side too, run_binance raises ValueError when both sides are open
+                print("liquidating", self.symbol, self.exchange)
+                return Position(
+                    symbol=self.symbol,
+                    notional=0.0,
+                    reduce_only=True,
+                    sz_type=self.SZ_TYPE_QTY,
+                    qty=0.0,
+                    target=True,
+                )
+            is_qty = self.sz_type == self.SZ_TYPE_QTY
             return Position(
                 symbol=self.symbol,
-                notional=notional,
+                notional=0.0 if is_qty else self.sz,
                 reduce_only=self.reduce_only,
+                sz_type=self.sz_type,
+                qty=self.sz if is_qty else 0.0,
             )
 
     def save(self, *args, **kwargs):
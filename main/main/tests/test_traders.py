@@ -86,3 +86,48 @@ class TestRustTrader(TestCase):
             id__in=[btc_long_order.id, eth_short_order.id]
         ).execute()
         print(result)
+
+    @override_settings(DRYRUN=True)
+    def test_rust_trader_paper_binance(self):
+        order = Order.objects.create(
+            exchange=settings.BINANCE_FUTURES_STRING,
+            symbol="BTCUSDT",
+            _type="l",
+            sz=200,
+            sz_type="n",
+            liquidated=False,
+            reduce_only=False,
+            price_expected=0,
+            strategy="test",
+        )
+        # without an interrupt a paper run only returns once the simulator filled it
+        result = Order.objects.filter(id__in=[order.id]).execute()
+        result = result[0]
+        self.assertEqual(result["status"], "filled")
+        self.assertGreater(result["matched_qty"], 0)
+        self.assertIsNotNone(result["avg_entry"])
+        # the simulated fills never reach the order
+        order = Order.objects.get(id=order.id)
+        self.assertEqual(order.dry_run, True)
+        self.assertEqual(order.executed, False)
+        self.assertEqual(order.price_actual, None)
+        self.assertEqual(order.matched_qty, None)
+
+    def test_rust_trader_validate_binance(self):
+        order = Order.objects.create(
+            exchange=settings.BINANCE_FUTURES_STRING,
+            symbol="BTCUSDT",
+            _type="l",
+            sz=200,
+            sz_type="n",
+            liquidated=False,
+            reduce_only=False,
+            price_expected=0,
+            strategy="test",
+        )
+        result = Order.objects.filter(id__in=[order.id]).validate()
+        result = result[0]
+        order = Order.objects.get(id=order.id)
+        self.assertEqual(result["status"], "accepted")
+        self.assertEqual(order.error, None)
+        self.assertEqual(order.executed, False)